@@ -1,5 +1,4 @@
-#![feature(proc_macro)]
-#![feature(specialization)]
+#![cfg_attr(feature = "macros", feature(proc_macro))]
 
 //! A macro for writing HTML templates.
 //!
@@ -7,28 +6,49 @@
 //! guide, check out the [book] instead.
 //!
 //! [book]: https://maud.lambda.xyz/
+//!
+//! The macros need a nightly compiler. With `default-features = false`, they
+//! are left out, and the rest of the crate builds on stable Rust; this lets
+//! a library implement `Render` for its types without depending on nightly.
 
 #![doc(html_root_url = "https://docs.rs/maud/0.17.2")]
 
+#[cfg(feature = "futures")] extern crate futures;
 #[cfg(feature = "iron")] extern crate iron;
 #[cfg(feature = "rocket")] extern crate rocket;
+#[cfg(feature = "serde")] extern crate serde as serde_crate;
 
 extern crate maud_htmlescape;
-extern crate maud_macros;
+#[macro_use] extern crate lazy_static;
+#[cfg(feature = "macros")] extern crate maud_macros;
+
+mod pretty;
 
+use std::borrow::Cow;
 use std::fmt::{self, Write};
+use std::io;
+use std::iter::{FromIterator, Peekable};
+use std::ops::{Add, AddAssign};
+use std::panic;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock, mpsc};
+use std::thread;
 
-pub use maud_macros::{html, html_debug};
+#[cfg(feature = "macros")]
+pub use maud_macros::{html, html_async, html_debug, html_fn, html_static, html_stream, html_to, xml};
 
 /// Represents a type that can be rendered as HTML.
 ///
-/// If your type implements [`Display`][1], then it will implement this
-/// trait automatically through a blanket impl.
+/// A splice like `(foo)` accepts any type which implements this trait. It
+/// also accepts any type which implements [`Display`][1], in which case the
+/// formatted value is escaped; wrap the value in
+/// [`Escaped`](struct.Escaped.html) to get a `Render` impl for it outside a
+/// splice.
 ///
 /// [1]: https://doc.rust-lang.org/std/fmt/trait.Display.html
 ///
-/// On the other hand, if your type has a custom HTML representation,
-/// then you can implement `Render` by hand. To do this, override
+/// If your type has a custom HTML representation, then you can implement
+/// `Render` by hand. To do this, override
 /// either the `.render()` or `.render_to()` methods; since each is
 /// defined in terms of the other, you only need to implement one of
 /// them. See the example below.
@@ -74,11 +94,246 @@ pub trait Render {
     fn render_to(&self, buffer: &mut String) {
         buffer.push_str(&self.render().into_string());
     }
+
+    /// Writes a representation of `self` to the given writer.
+    ///
+    /// This is used by the `html_to!` macro. Its default implementation
+    /// renders `self` to a temporary buffer, then writes that buffer out;
+    /// you may override it to write to the writer directly instead.
+    ///
+    /// As with `.render_to()`, no further escaping is performed on data
+    /// written to the writer.
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        let mut buffer = String::new();
+        self.render_to(&mut buffer);
+        w.write_str(&buffer)
+    }
+
+    /// Appends a representation of `self` to the given buffer, for use in
+    /// an XML document.
+    ///
+    /// This is used by the `xml!` macro. The default implementation calls
+    /// `.render_to()`; types which escape text themselves should override
+    /// it to escape single quotes as well, using `Escaper::xml()`.
+    fn render_xml_to(&self, buffer: &mut String) {
+        self.render_to(buffer);
+    }
+}
+
+/// Implements `Render` by rendering the value that `self` points to.
+macro_rules! delegate_render {
+    () => {
+        fn render(&self) -> Markup {
+            (**self).render()
+        }
+
+        fn render_to(&self, w: &mut String) {
+            (**self).render_to(w);
+        }
+
+        fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+            (**self).render_fmt(w)
+        }
+
+        fn render_xml_to(&self, w: &mut String) {
+            (**self).render_xml_to(w);
+        }
+    }
+}
+
+impl<'a, T: Render + ?Sized> Render for &'a T {
+    delegate_render!();
+}
+
+impl<'a, T: Render + ?Sized> Render for &'a mut T {
+    delegate_render!();
+}
+
+impl<T: Render + ?Sized> Render for Box<T> {
+    delegate_render!();
+}
+
+impl<T: Render + ?Sized> Render for Rc<T> {
+    delegate_render!();
+}
+
+impl<T: Render + ?Sized> Render for Arc<T> {
+    delegate_render!();
+}
+
+impl<'a, T: Render + ToOwned + ?Sized> Render for Cow<'a, T> {
+    delegate_render!();
+}
+
+/// Renders nothing for `None`.
+impl<T: Render> Render for Option<T> {
+    fn render_to(&self, w: &mut String) {
+        if let Some(ref value) = *self {
+            value.render_to(w);
+        }
+    }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        match *self {
+            Some(ref value) => value.render_fmt(w),
+            None => Ok(()),
+        }
+    }
+
+    fn render_xml_to(&self, w: &mut String) {
+        if let Some(ref value) = *self {
+            value.render_xml_to(w);
+        }
+    }
+}
+
+/// Renders an `Err` through the hook set by
+/// [`set_error_hook`](fn.set_error_hook.html), which renders nothing by
+/// default.
+impl<T: Render, E: fmt::Display> Render for Result<T, E> {
+    fn render_to(&self, w: &mut String) {
+        match *self {
+            Ok(ref value) => value.render_to(w),
+            Err(ref error) => render_error(error, w),
+        }
+    }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        match *self {
+            Ok(ref value) => value.render_fmt(w),
+            Err(ref error) => {
+                let mut buffer = String::new();
+                render_error(error, &mut buffer);
+                w.write_str(&buffer)
+            },
+        }
+    }
+
+    fn render_xml_to(&self, w: &mut String) {
+        match *self {
+            Ok(ref value) => value.render_xml_to(w),
+            Err(ref error) => render_error(error, w),
+        }
+    }
+}
+
+/// The signature of the hook set by `set_error_hook`.
+type ErrorHook = fn(&fmt::Display, &mut String);
+
+lazy_static! {
+    /// The hook set by `set_error_hook`, if any.
+    static ref ERROR_HOOK: RwLock<Option<ErrorHook>> = RwLock::new(None);
+}
+
+/// Sets the function that renders the error when an `Err` is spliced into a
+/// template.
+///
+/// By default, errors are not rendered at all, since their messages may not
+/// be meant for the people reading the page. The hook is global, like a
+/// panic hook, so it's best set once at startup.
+///
+/// As with `Render::render_to()`, the hook must escape anything it writes
+/// to the buffer.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// maud::set_error_hook(|error, buffer| {
+///     buffer.push_str("<span class=\"error\">");
+///     maud::Escaped(error).render_to(buffer);
+///     buffer.push_str("</span>");
+/// });
+/// ```
+pub fn set_error_hook(hook: fn(&fmt::Display, &mut String)) {
+    *ERROR_HOOK.write().unwrap_or_else(|e| e.into_inner()) = Some(hook);
+}
+
+/// Removes the hook set by `set_error_hook` and returns it, so that errors
+/// are no longer rendered.
+pub fn take_error_hook() -> Option<fn(&fmt::Display, &mut String)> {
+    ERROR_HOOK.write().unwrap_or_else(|e| e.into_inner()).take()
+}
+
+fn render_error(error: &fmt::Display, buffer: &mut String) {
+    // Copy the hook out first, so the lock isn't held while it runs
+    let hook = *ERROR_HOOK.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(hook) = hook {
+        hook(error, buffer);
+    }
+}
+
+/// Renders each item in turn.
+impl<T: Render> Render for [T] {
+    fn render_to(&self, w: &mut String) {
+        for item in self {
+            item.render_to(w);
+        }
+    }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        for item in self {
+            item.render_fmt(w)?;
+        }
+        Ok(())
+    }
+
+    fn render_xml_to(&self, w: &mut String) {
+        for item in self {
+            item.render_xml_to(w);
+        }
+    }
+}
+
+impl<T: Render> Render for Vec<T> {
+    delegate_render!();
 }
 
-impl<T: fmt::Display + ?Sized> Render for T {
-    default fn render_to(&self, w: &mut String) {
-        let _ = write!(Escaper::new(w), "{}", self);
+/// Renders the items of an iterator, with a separator between each pair.
+///
+/// The iterator is cloned each time the `Join` is rendered, so it should be
+/// cheap to clone: a reference to a collection, say, or an iterator over
+/// one.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let ponies = ["Pinkie Pie", "Rarity", "Applejack"];
+/// let markup = html!(p "Best ponies: " (Join(&ponies, ", ")));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Join<I, S>(pub I, pub S);
+
+impl<I, S> Render for Join<I, S> where
+    I: IntoIterator + Clone,
+    I::Item: Render,
+    S: Render,
+{
+    fn render_to(&self, w: &mut String) {
+        for (i, item) in self.0.clone().into_iter().enumerate() {
+            if i > 0 {
+                self.1.render_to(w);
+            }
+            item.render_to(w);
+        }
+    }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        for (i, item) in self.0.clone().into_iter().enumerate() {
+            if i > 0 {
+                self.1.render_fmt(w)?;
+            }
+            item.render_fmt(w)?;
+        }
+        Ok(())
+    }
+
+    fn render_xml_to(&self, w: &mut String) {
+        for (i, item) in self.0.clone().into_iter().enumerate() {
+            if i > 0 {
+                self.1.render_xml_to(w);
+            }
+            item.render_xml_to(w);
+        }
     }
 }
 
@@ -86,22 +341,163 @@ impl Render for String {
     fn render_to(&self, w: &mut String) {
         let _ = Escaper::new(w).write_str(self);
     }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        Escaper::new(w).write_str(self)
+    }
+
+    fn render_xml_to(&self, w: &mut String) {
+        let _ = Escaper::xml(w).write_str(self);
+    }
 }
 
 impl Render for str {
     fn render_to(&self, w: &mut String) {
         let _ = Escaper::new(w).write_str(self);
     }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        Escaper::new(w).write_str(self)
+    }
+
+    fn render_xml_to(&self, w: &mut String) {
+        let _ = Escaper::xml(w).write_str(self);
+    }
+}
+
+macro_rules! impl_render_with_display {
+    ($($ty:ty)*) => {
+        $(
+            impl Render for $ty {
+                fn render_to(&self, w: &mut String) {
+                    Escaped(self).render_to(w);
+                }
+
+                fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+                    Escaped(self).render_fmt(w)
+                }
+
+                fn render_xml_to(&self, w: &mut String) {
+                    Escaped(self).render_xml_to(w);
+                }
+            }
+        )*
+    }
+}
+
+impl_render_with_display! {
+    char bool
+    i8 i16 i32 i64 isize
+    u8 u16 u32 u64 usize
+    f32 f64
+}
+
+impl<'a> Render for fmt::Arguments<'a> {
+    fn render_to(&self, w: &mut String) {
+        Escaped(self).render_to(w);
+    }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        Escaped(self).render_fmt(w)
+    }
+
+    fn render_xml_to(&self, w: &mut String) {
+        Escaped(self).render_xml_to(w);
+    }
+}
+
+/// A fragment of markup which is rendered by a closure, as returned by
+/// [`from_fn`](fn.from_fn.html) and `html_fn!`.
+#[derive(Clone, Copy)]
+pub struct FromFn<F>(F);
+
+/// Creates a fragment of markup which is rendered by calling the given
+/// closure with the output buffer.
+///
+/// Nothing is rendered until the fragment is spliced into a template, and
+/// then the closure writes straight into that template's buffer. This lets
+/// a helper take or return a fragment without allocating a `Markup` for it.
+///
+/// As with `Render::render_to()`, the closure must escape anything it
+/// writes to the buffer. The `html_fn!` macro builds such a closure from a
+/// template, so you rarely need to write one by hand.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// fn layout<B: Render>(title: &str, body: B) -> Markup {
+///     html! {
+///         head { title (title) }
+///         body { (body) }
+///     }
+/// }
+///
+/// let name = "Pinkie Pie";
+/// let page = layout("Hello", html_fn!(p { "Hello, " (name) "!" }));
+/// let page = layout("Hello", maud::from_fn(|buffer| buffer.push_str("<hr>")));
+/// ```
+pub fn from_fn<F: Fn(&mut String)>(f: F) -> FromFn<F> {
+    FromFn(f)
+}
+
+impl<F: Fn(&mut String)> Render for FromFn<F> {
+    fn render_to(&self, w: &mut String) {
+        (self.0)(w);
+    }
+}
+
+/// A wrapper that renders the inner value by escaping its [`Display`][1]
+/// output.
+///
+/// [1]: https://doc.rust-lang.org/std/fmt/trait.Display.html
+///
+/// Splices do this already for types which don't implement `Render`, so
+/// you only need `Escaped` where a `Render` is expected in plain Rust code,
+/// such as an argument to a generic function.
+///
+/// With the `serde` feature enabled, `Escaped` is serialized and
+/// deserialized as its inner value. This makes it safe to deserialize from an
+/// untrusted source, such as a JSON request body, unlike markup read through
+/// [`serde::trusted`](serde/trusted/index.html).
+///
+/// # Example
+///
+/// ```rust,ignore
+/// fn list_item<T: Render>(item: T) -> Markup {
+///     html!(li (item))
+/// }
+///
+/// let markup = list_item(Escaped(Ipv4Addr::new(127, 0, 0, 1)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Escaped<T: fmt::Display>(pub T);
+
+impl<T: fmt::Display> Render for Escaped<T> {
+    fn render_to(&self, w: &mut String) {
+        let _ = write!(Escaper::new(w), "{}", self.0);
+    }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        write!(Escaper::new(w), "{}", self.0)
+    }
+
+    fn render_xml_to(&self, w: &mut String) {
+        let _ = write!(Escaper::xml(w), "{}", self.0);
+    }
 }
 
 /// A wrapper that renders the inner value without escaping.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct PreEscaped<T: AsRef<str>>(pub T);
 
 impl<T: AsRef<str>> Render for PreEscaped<T> {
     fn render_to(&self, w: &mut String) {
         w.push_str(self.0.as_ref());
     }
+
+    fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+        w.write_str(self.0.as_ref())
+    }
 }
 
 /// A block of markup is a string that does not need to be escaped.
@@ -116,69 +512,1219 @@ impl<T: AsRef<str> + Into<String>> PreEscaped<T> {
     }
 }
 
+impl<T: AsRef<str>> PreEscaped<T> {
+    /// Returns the markup as a string slice.
+    pub fn as_str(&self) -> &str {
+        self.0.as_ref()
+    }
+
+    /// Returns the length of the markup, in bytes.
+    pub fn len(&self) -> usize {
+        self.as_str().len()
+    }
+
+    /// Returns whether the markup is empty.
+    pub fn is_empty(&self) -> bool {
+        self.as_str().is_empty()
+    }
+
+    /// Returns a copy of the markup with its block-level elements indented,
+    /// one per line. This is handy for debugging, and for snapshot tests.
+    ///
+    /// Line breaks are only added between block-level elements such as
+    /// `div` and `li`, where browsers ignore them under their default
+    /// styles. The contents of paragraphs, inline elements, and
+    /// whitespace-sensitive elements like `pre` and `textarea` are left
+    /// untouched.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let markup = html!(ul { li "Pinkie" li "Rarity" });
+    /// assert_eq!(markup.pretty().into_string(), "<ul>\n  <li>Pinkie</li>\n  <li>Rarity</li>\n</ul>");
+    /// ```
+    pub fn pretty(&self) -> Markup {
+        PreEscaped(pretty::pretty(self.0.as_ref()))
+    }
+}
+
 impl<T: AsRef<str> + Into<String>> Into<String> for PreEscaped<T> {
     fn into(self) -> String {
         self.into_string()
     }
 }
 
+impl Markup {
+    /// Concatenates the given fragments, with a separator between each pair.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let items = ponies.iter().map(|pony| html!(b (pony)));
+    /// let markup = Markup::join(items, html!(br;));
+    /// ```
+    pub fn join<I, T, S>(fragments: I, separator: PreEscaped<S>) -> Markup where
+        I: IntoIterator<Item=PreEscaped<T>>,
+        T: AsRef<str>,
+        S: AsRef<str>,
+    {
+        let mut result = Markup::default();
+        for (i, fragment) in fragments.into_iter().enumerate() {
+            if i > 0 {
+                result.0.push_str(separator.as_str());
+            }
+            result.0.push_str(fragment.as_str());
+        }
+        result
+    }
+}
+
+/// Writes the markup as is, without escaping.
+impl<T: AsRef<str>> fmt::Display for PreEscaped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<T: AsRef<str>> Add<PreEscaped<T>> for Markup {
+    type Output = Markup;
+
+    fn add(mut self, other: PreEscaped<T>) -> Markup {
+        self += other;
+        self
+    }
+}
+
+impl<'a, T: AsRef<str>> Add<&'a PreEscaped<T>> for Markup {
+    type Output = Markup;
+
+    fn add(mut self, other: &'a PreEscaped<T>) -> Markup {
+        self += other;
+        self
+    }
+}
+
+impl<T: AsRef<str>> AddAssign<PreEscaped<T>> for Markup {
+    fn add_assign(&mut self, other: PreEscaped<T>) {
+        self.0.push_str(other.as_str());
+    }
+}
+
+impl<'a, T: AsRef<str>> AddAssign<&'a PreEscaped<T>> for Markup {
+    fn add_assign(&mut self, other: &'a PreEscaped<T>) {
+        self.0.push_str(other.as_str());
+    }
+}
+
+impl<T: AsRef<str>> FromIterator<PreEscaped<T>> for Markup {
+    fn from_iter<I: IntoIterator<Item=PreEscaped<T>>>(iter: I) -> Markup {
+        let mut result = Markup::default();
+        result.extend(iter);
+        result
+    }
+}
+
+impl<T: AsRef<str>> Extend<PreEscaped<T>> for Markup {
+    fn extend<I: IntoIterator<Item=PreEscaped<T>>>(&mut self, iter: I) {
+        for fragment in iter {
+            *self += fragment;
+        }
+    }
+}
+
 pub use maud_htmlescape::Escaper;
 
-/// The literal string `<!DOCTYPE html>`.
+/// A URL which is known to be safe, even if it uses a `javascript:` or
+/// `data:` scheme.
 ///
-/// # Example
+/// When a value is spliced into a URL attribute (`href`, `src`, `action`, or
+/// `formaction`), Maud replaces any URL with one of those schemes with
+/// `about:invalid#maud`, since it could be used to run a script. Wrap the
+/// value in `TrustedUrl` to opt out of this check. The URL is still
+/// HTML-escaped as usual.
 ///
-/// A minimal web page:
+/// # Example
 ///
 /// ```rust,ignore
-/// use maud::DOCTYPE;
+/// let icon = "data:image/png;base64,iVBORw0KGgo=";
+/// let markup = html!(img src=(TrustedUrl(icon)););
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrustedUrl<T: AsRef<str>>(pub T);
+
+impl<T: AsRef<str>> Render for TrustedUrl<T> {
+    fn render_to(&self, w: &mut String) {
+        let _ = Escaper::new(w).write_str(self.0.as_ref());
+    }
+}
+
+/// A template which is rendered in chunks, as returned by `html_stream!`.
 ///
-/// let markup = html! {
-///     (DOCTYPE)
-///     html {
-///         head {
-///             meta charset="utf-8";
-///             title "Test page"
-///         }
-///         body {
-///             p "Hello, world!"
-///         }
-///     }
+/// Each `@flush` in the template marks the end of a chunk. This lets you
+/// send the start of a page (say, the `<head>`) before the rest of it has
+/// been computed.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let page = html_stream! {
+///     head { title "Slow page" }
+///     @flush
+///     body { (compute_body()) }
 /// };
+/// page.write_to(&mut io::stdout())?;
 /// ```
-pub const DOCTYPE: PreEscaped<&'static str> = PreEscaped("<!DOCTYPE html>");
+///
+/// `.write_to()` and `.for_each_chunk()` render the template on the current
+/// thread. If the template can be sent to another thread, then it can also
+/// be consumed as an iterator of chunks, or as a `Stream` with the
+/// `futures` feature; either way, each chunk is available as soon as it's
+/// rendered.
+pub struct Streaming<F> {
+    render: Option<F>,
+}
 
-#[cfg(feature = "iron")]
-mod iron_support {
-    use std::io;
-    use iron::headers::ContentType;
-    use iron::modifier::{Modifier, Set};
-    use iron::modifiers::Header;
-    use iron::response::{Response, WriteBody};
-    use PreEscaped;
+impl<F: FnOnce(&mut macro_private::ChunkedSink)> Streaming<F> {
+    #[doc(hidden)]
+    pub fn new(render: F) -> Streaming<F> {
+        Streaming { render: Some(render) }
+    }
 
-    impl Modifier<Response> for PreEscaped<String> {
-        fn modify(self, response: &mut Response) {
-            response
-                .set_mut(Header(ContentType::html()))
-                .set_mut(Box::new(self) as Box<WriteBody>);
+    /// Renders the template, calling the given function with each chunk as
+    /// soon as it's done.
+    pub fn for_each_chunk<G: FnMut(String)>(mut self, mut f: G) {
+        if let Some(render) = self.render.take() {
+            let mut sink = macro_private::ChunkedSink::new(&mut f);
+            render(&mut sink);
+            sink.flush();
         }
     }
 
-    impl WriteBody for PreEscaped<String> {
-        fn write_body(&mut self, body: &mut io::Write) -> io::Result<()> {
-            self.0.write_body(body)
+    /// Renders the template to the given writer, flushing the writer after
+    /// each chunk.
+    pub fn write_to<W: io::Write + ?Sized>(self, w: &mut W) -> io::Result<()> {
+        let mut result = Ok(());
+        self.for_each_chunk(|chunk| if result.is_ok() {
+            result = w.write_all(chunk.as_bytes()).and_then(|()| w.flush());
+        });
+        result
+    }
+}
+
+impl<F> Streaming<F> where
+    F: FnOnce(&mut macro_private::ChunkedSink) + Send + 'static,
+{
+    /// Renders the template on a new thread, returning a `Stream` which
+    /// yields each chunk as soon as it's done.
+    ///
+    /// This requires the `futures` feature.
+    #[cfg(feature = "futures")]
+    pub fn into_stream(self) -> ChunkStream {
+        let (sender, receiver) = futures::sync::mpsc::unbounded();
+        let thread = thread::spawn(move || {
+            // If the receiver is dropped, the rest of the chunks are ignored
+            self.for_each_chunk(|chunk| { let _ = sender.unbounded_send(chunk); });
+        });
+        ChunkStream {
+            receiver,
+            thread: Some(thread),
         }
     }
 }
 
-#[cfg(feature = "rocket")]
+impl<F> IntoIterator for Streaming<F> where
+    F: FnOnce(&mut macro_private::ChunkedSink) + Send + 'static,
+{
+    type Item = String;
+    type IntoIter = Chunks;
+
+    /// Renders the template on a new thread, returning an iterator which
+    /// yields each chunk as soon as it's done.
+    ///
+    /// If the template borrows from its surroundings, and so can't be sent
+    /// to another thread, use `.for_each_chunk()` or `.write_to()` instead.
+    fn into_iter(self) -> Chunks {
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            // If the receiver is dropped, the rest of the chunks are ignored
+            self.for_each_chunk(|chunk| { let _ = sender.send(chunk); });
+        });
+        Chunks {
+            receiver,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// An iterator over the chunks of a `Streaming` template, as they're
+/// rendered.
+///
+/// If the template panics, then the panic is passed on once the chunks
+/// before it have been consumed.
+pub struct Chunks {
+    receiver: mpsc::Receiver<String>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Iterator for Chunks {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        match self.receiver.recv() {
+            Ok(chunk) => Some(chunk),
+            Err(mpsc::RecvError) => {
+                join_render_thread(&mut self.thread);
+                None
+            },
+        }
+    }
+}
+
+/// A `Stream` of the chunks of a `Streaming` template, as returned by
+/// `.into_stream()`.
+///
+/// As with `Chunks`, a panic in the template is passed on at the end of the
+/// stream.
+#[cfg(feature = "futures")]
+pub struct ChunkStream {
+    receiver: futures::sync::mpsc::UnboundedReceiver<String>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// Waits for the thread which renders a template to finish, and passes on
+/// its panic if it had one.
+fn join_render_thread(thread: &mut Option<thread::JoinHandle<()>>) {
+    if let Some(thread) = thread.take() {
+        if let Err(payload) = thread.join() {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+/// An adapter that lets `html_to!` write to an [`io::Write`][1], such as a
+/// file or socket.
+///
+/// [1]: https://doc.rust-lang.org/std/io/trait.Write.html
+///
+/// Since a `fmt::Write` can't return an I/O error, the adapter keeps hold of
+/// the first error instead, and stops writing from then on. Call
+/// `.finish()` afterward to get that error back.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let mut writer = IoWriter::new(io::stdout());
+/// let result = html_to!(writer, p { "Hello, world!" });
+/// writer.finish(result)?;
+/// ```
+pub struct IoWriter<W: io::Write> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    /// Creates an `IoWriter` which writes to the given `io::Write`.
+    pub fn new(inner: W) -> IoWriter<W> {
+        IoWriter {
+            inner,
+            error: None,
+        }
+    }
+
+    /// Takes the result of an `html_to!` call, and returns the underlying
+    /// writer, or the first I/O error that was encountered.
+    ///
+    /// If there was no I/O error, but the result is still an `Err` (due to
+    /// a `Display` impl failing, say), then that's converted to an
+    /// `io::Error` instead.
+    pub fn finish(self, result: fmt::Result) -> io::Result<W> {
+        match (self.error, result) {
+            (Some(error), _) => Err(error),
+            (None, Ok(())) => Ok(self.inner),
+            (None, Err(fmt::Error)) => Err(io::Error::new(io::ErrorKind::Other, "formatter error")),
+        }
+    }
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.error.is_some() {
+            return Err(fmt::Error);
+        }
+        match self.inner.write_all(s.as_bytes()) {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                self.error = Some(error);
+                Err(fmt::Error)
+            },
+        }
+    }
+}
+
+/// The literal string `<!DOCTYPE html>`.
+///
+/// # Example
+///
+/// A minimal web page:
+///
+/// ```rust,ignore
+/// use maud::DOCTYPE;
+///
+/// let markup = html! {
+///     (DOCTYPE)
+///     html {
+///         head {
+///             meta charset="utf-8";
+///             title "Test page"
+///         }
+///         body {
+///             p "Hello, world!"
+///         }
+///     }
+/// };
+/// ```
+pub const DOCTYPE: PreEscaped<&'static str> = PreEscaped("<!DOCTYPE html>");
+
+/// The literal string `<?xml version="1.0" encoding="UTF-8"?>`.
+///
+/// # Example
+///
+/// A minimal Atom feed:
+///
+/// ```rust,ignore
+/// use maud::XML_DECLARATION;
+///
+/// let markup = xml! {
+///     (XML_DECLARATION)
+///     feed xmlns="http://www.w3.org/2005/Atom" {
+///         title "Example feed"
+///         link href="https://example.com/";
+///     }
+/// };
+/// ```
+pub const XML_DECLARATION: PreEscaped<&'static str> =
+    PreEscaped("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+
+/// Information about the current iteration of a loop, as yielded by
+/// [`loop_info`](fn.loop_info.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopInfo {
+    /// The index of the current item, starting from zero.
+    pub index: usize,
+    /// Whether this is the first item.
+    pub first: bool,
+    /// Whether this is the last item.
+    pub last: bool,
+}
+
+/// Pairs each item of an iterator with a [`LoopInfo`](struct.LoopInfo.html)
+/// describing its position.
+///
+/// This is useful for adding separators, or striping every other row:
+///
+/// ```rust,ignore
+/// let markup = html! {
+///     @for (l, pony) in maud::loop_info(&ponies) {
+///         span.odd[l.index % 2 == 1] (pony)
+///         @if !l.last { ", " }
+///     }
+/// };
+/// ```
+pub fn loop_info<I: IntoIterator>(iter: I) -> LoopInfoIter<I::IntoIter> {
+    LoopInfoIter {
+        iter: iter.into_iter().peekable(),
+        index: 0,
+    }
+}
+
+/// The iterator returned by [`loop_info`](fn.loop_info.html).
+pub struct LoopInfoIter<I: Iterator> {
+    iter: Peekable<I>,
+    index: usize,
+}
+
+impl<I: Iterator> Iterator for LoopInfoIter<I> {
+    type Item = (LoopInfo, I::Item);
+
+    fn next(&mut self) -> Option<(LoopInfo, I::Item)> {
+        let item = match self.iter.next() {
+            Some(item) => item,
+            None => return None,
+        };
+        let info = LoopInfo {
+            index: self.index,
+            first: self.index == 0,
+            last: self.iter.peek().is_none(),
+        };
+        self.index += 1;
+        Some((info, item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// The name of an element, checked to be safe for use in a `@tag`
+/// expression.
+///
+/// ```rust,ignore
+/// let level = 2;
+/// let markup = html! {
+///     @tag (format!("h{}", level)) { "Hello!" }
+/// };
+/// ```
+///
+/// A valid name starts with an ASCII letter, followed by ASCII letters,
+/// digits, `-`, `.`, `_`, `:` or non-ASCII characters. In particular, it
+/// can't contain whitespace, quotes or angle brackets, which would break out
+/// of the surrounding tag.
+///
+/// A `@tag` expression also refuses to make `script` or `style` elements,
+/// since it can't escape their contents; write those out in the template.
+#[derive(Debug, Clone, Copy)]
+pub struct TagName<T: AsRef<str>>(T);
+
+impl<T: AsRef<str>> TagName<T> {
+    /// Checks the given element name, returning `None` if it's not valid.
+    pub fn new(name: T) -> Option<TagName<T>> {
+        if maud_htmlescape::is_valid_element_name(name.as_ref()) {
+            Some(TagName(name))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the name as a string slice.
+    pub fn as_str(&self) -> &str {
+        self.0.as_ref()
+    }
+
+    /// Converts the `TagName` back into its inner value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: AsRef<str>> AsRef<str> for TagName<T> {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+/// Represents a type that can be used as an attribute value in an attribute
+/// spread.
+///
+/// An attribute spread takes an iterator of name-value pairs, and renders
+/// each of them as an attribute:
+///
+/// ```rust,ignore
+/// let mut attrs = BTreeMap::new();
+/// attrs.insert("aria-label", "Close");
+/// attrs.insert("data-dismiss", "modal");
+///
+/// let markup = html! {
+///     button ..(&attrs) "×"
+/// };
+/// ```
+///
+/// Values that implement [`Render`](trait.Render.html) are escaped as
+/// usual. A `bool` renders an empty attribute when `true`, and an `Option`
+/// renders its inner value when `Some`; both omit the attribute otherwise.
+///
+/// Pairs whose names are not valid attribute names (because they contain
+/// whitespace, quotes, or `=`, say) are skipped. A `class` from a spread is
+/// added to the classes written in the template, and an `id` is used only if
+/// the template doesn't give the element one.
+pub trait AttributeValue {
+    /// Appends the value of the attribute to `buffer`, escaped, and returns
+    /// `AttributeKind::Value`. Returns one of the other kinds, without
+    /// appending anything, if the attribute should be left out or written
+    /// without a value.
+    fn render_value_to(&self, buffer: &mut String) -> AttributeKind;
+}
+
+/// How an attribute in an attribute spread is written, as returned by
+/// [`AttributeValue::render_value_to`](trait.AttributeValue.html#tymethod.render_value_to).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributeKind {
+    /// The attribute is left out.
+    Omitted,
+    /// The attribute is written without a value, as in `<input disabled>`.
+    Empty,
+    /// The attribute is written with the value appended to the buffer.
+    Value,
+}
+
+macro_rules! impl_attribute_value {
+    ($($ty:ty)*) => {
+        $(
+            impl AttributeValue for $ty {
+                fn render_value_to(&self, buffer: &mut String) -> AttributeKind {
+                    self.render_to(buffer);
+                    AttributeKind::Value
+                }
+            }
+        )*
+    }
+}
+
+impl_attribute_value! {
+    str String char
+    i8 i16 i32 i64 isize
+    u8 u16 u32 u64 usize
+    f32 f64
+}
+
+impl<'a> AttributeValue for Cow<'a, str> {
+    fn render_value_to(&self, buffer: &mut String) -> AttributeKind {
+        (**self).render_value_to(buffer)
+    }
+}
+
+impl<T: AsRef<str>> AttributeValue for PreEscaped<T> {
+    fn render_value_to(&self, buffer: &mut String) -> AttributeKind {
+        self.render_to(buffer);
+        AttributeKind::Value
+    }
+}
+
+impl AttributeValue for bool {
+    fn render_value_to(&self, _: &mut String) -> AttributeKind {
+        if *self {
+            AttributeKind::Empty
+        } else {
+            AttributeKind::Omitted
+        }
+    }
+}
+
+impl<T: AttributeValue> AttributeValue for Option<T> {
+    fn render_value_to(&self, buffer: &mut String) -> AttributeKind {
+        match *self {
+            Some(ref value) => value.render_value_to(buffer),
+            None => AttributeKind::Omitted,
+        }
+    }
+}
+
+impl<'a, T: AttributeValue + ?Sized> AttributeValue for &'a T {
+    fn render_value_to(&self, buffer: &mut String) -> AttributeKind {
+        (**self).render_value_to(buffer)
+    }
+}
+
+#[doc(hidden)]
+pub mod macro_private {
+    use maud_htmlescape::{is_valid_attribute_name, is_valid_element_name};
+    use std::cmp;
+    use std::fmt::{self, Write};
+    use std::mem;
+    use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicUsize, Ordering};
+    use {AttributeKind, AttributeValue, Escaped, PreEscaped, Render, TagName, TrustedUrl};
+
+    /// The output of an `html!`, `html_to!`, or `html_stream!` invocation.
+    pub trait Output {
+        fn push_str(&mut self, s: &str);
+        fn render<T: Render + ?Sized>(&mut self, value: &T);
+
+        /// Like `render`, but used by `xml!`.
+        fn render_xml<T: Render + ?Sized>(&mut self, value: &T) {
+            let mut buffer = String::new();
+            value.render_xml_to(&mut buffer);
+            self.push_str(&buffer);
+        }
+
+        /// Called on `@flush`. This does nothing by default.
+        fn flush(&mut self) {}
+
+        /// Reserves space for at least `additional` more bytes. This does
+        /// nothing by default.
+        fn reserve(&mut self, _additional: usize) {}
+    }
+
+    impl<'a, O: Output + ?Sized> Output for &'a mut O {
+        fn push_str(&mut self, s: &str) {
+            (**self).push_str(s);
+        }
+
+        fn render<T: Render + ?Sized>(&mut self, value: &T) {
+            (**self).render(value);
+        }
+
+        fn render_xml<T: Render + ?Sized>(&mut self, value: &T) {
+            (**self).render_xml(value);
+        }
+
+        fn flush(&mut self) {
+            (**self).flush();
+        }
+
+        fn reserve(&mut self, additional: usize) {
+            (**self).reserve(additional);
+        }
+    }
+
+    impl Output for String {
+        fn push_str(&mut self, s: &str) {
+            String::push_str(self, s);
+        }
+
+        fn render<T: Render + ?Sized>(&mut self, value: &T) {
+            value.render_to(self);
+        }
+
+        fn render_xml<T: Render + ?Sized>(&mut self, value: &T) {
+            value.render_xml_to(self);
+        }
+
+        fn reserve(&mut self, additional: usize) {
+            String::reserve(self, additional);
+        }
+    }
+
+    /// The output of an `html_to!` invocation, which writes to a
+    /// `fmt::Write`.
+    ///
+    /// After the first error, nothing more is written.
+    pub struct Sink<'a> {
+        writer: &'a mut fmt::Write,
+        result: fmt::Result,
+    }
+
+    impl<'a> Sink<'a> {
+        pub fn new(writer: &'a mut fmt::Write) -> Sink<'a> {
+            Sink {
+                writer,
+                result: Ok(()),
+            }
+        }
+
+        pub fn push_str(&mut self, s: &str) {
+            if self.result.is_ok() {
+                self.result = self.writer.write_str(s);
+            }
+        }
+
+        pub fn finish(self) -> fmt::Result {
+            self.result
+        }
+    }
+
+    /// The output of an `html_stream!` invocation, which passes each chunk
+    /// to a callback.
+    pub struct ChunkedSink<'a> {
+        buffer: String,
+        callback: &'a mut FnMut(String),
+    }
+
+    impl<'a> ChunkedSink<'a> {
+        pub fn new(callback: &'a mut FnMut(String)) -> ChunkedSink<'a> {
+            ChunkedSink {
+                buffer: String::new(),
+                callback,
+            }
+        }
+
+        pub fn push_str(&mut self, s: &str) {
+            self.buffer.push_str(s);
+        }
+
+        pub fn flush(&mut self) {
+            if !self.buffer.is_empty() {
+                let chunk = mem::replace(&mut self.buffer, String::new());
+                (self.callback)(chunk);
+            }
+        }
+    }
+
+    impl<'a> Output for ChunkedSink<'a> {
+        fn push_str(&mut self, s: &str) {
+            ChunkedSink::push_str(self, s);
+        }
+
+        fn render<T: Render + ?Sized>(&mut self, value: &T) {
+            value.render_to(&mut self.buffer);
+        }
+
+        fn flush(&mut self) {
+            ChunkedSink::flush(self);
+        }
+
+        fn reserve(&mut self, additional: usize) {
+            self.buffer.reserve(additional);
+        }
+    }
+
+    impl<'a> Output for Sink<'a> {
+        fn push_str(&mut self, s: &str) {
+            Sink::push_str(self, s);
+        }
+
+        fn render<T: Render + ?Sized>(&mut self, value: &T) {
+            if self.result.is_ok() {
+                self.result = value.render_fmt(self.writer);
+            }
+        }
+    }
+
+    /// A reference to the value of a splice, as in `(foo)`.
+    ///
+    /// The `html!` macro renders a splice by calling a method on
+    /// `&Splice(&foo)`, through one of two traits: `RenderSplice`, which is
+    /// implemented on `Splice<T>` for `T: Render`, and `DisplaySplice`, which
+    /// is implemented on `&Splice<T>` for `T: Display`. Method lookup tries
+    /// the receiver as is before borrowing it again, so the first trait wins
+    /// when both apply.
+    pub struct Splice<'a, T: 'a + ?Sized>(pub &'a T);
+
+    /// Renders a splice whose value implements `Render`.
+    pub trait RenderSplice {
+        fn __maud_render_to<O: Output>(&self, output: &mut O);
+        fn __maud_render_xml<O: Output>(&self, output: &mut O);
+        fn __maud_render_url<O: Output>(&self, output: &mut O);
+        fn __maud_render_js<O: Output>(&self, output: &mut O);
+        fn __maud_render_css<O: Output>(&self, output: &mut O);
+    }
+
+    impl<'a, T: Render + ?Sized> RenderSplice for Splice<'a, T> {
+        fn __maud_render_to<O: Output>(&self, output: &mut O) {
+            output.render(self.0);
+        }
+
+        fn __maud_render_xml<O: Output>(&self, output: &mut O) {
+            output.render_xml(self.0);
+        }
+
+        fn __maud_render_url<O: Output>(&self, output: &mut O) {
+            render_url(self.0, output);
+        }
+
+        fn __maud_render_js<O: Output>(&self, output: &mut O) {
+            render_js(self.0, output);
+        }
+
+        fn __maud_render_css<O: Output>(&self, output: &mut O) {
+            render_css(self.0, output);
+        }
+    }
+
+    /// Renders a splice whose value implements `Display`, but not `Render`.
+    pub trait DisplaySplice {
+        fn __maud_render_to<O: Output>(&self, output: &mut O);
+        fn __maud_render_xml<O: Output>(&self, output: &mut O);
+        fn __maud_render_url<O: Output>(&self, output: &mut O);
+        fn __maud_render_js<O: Output>(&self, output: &mut O);
+        fn __maud_render_css<O: Output>(&self, output: &mut O);
+    }
+
+    impl<'a, 'b, T: fmt::Display + ?Sized> DisplaySplice for &'b Splice<'a, T> {
+        fn __maud_render_to<O: Output>(&self, output: &mut O) {
+            output.render(&Escaped(self.0));
+        }
+
+        fn __maud_render_xml<O: Output>(&self, output: &mut O) {
+            output.render_xml(&Escaped(self.0));
+        }
+
+        fn __maud_render_url<O: Output>(&self, output: &mut O) {
+            render_url(&Escaped(self.0), output);
+        }
+
+        fn __maud_render_js<O: Output>(&self, output: &mut O) {
+            render_js(&Escaped(self.0), output);
+        }
+
+        fn __maud_render_css<O: Output>(&self, output: &mut O) {
+            render_css(&Escaped(self.0), output);
+        }
+    }
+
+    // Pre-escaped text is trusted, so it's passed through as is, even where
+    // splices would otherwise be escaped as a URL, JavaScript, or CSS. These
+    // inherent methods take precedence over the trait methods.
+
+    impl<'a, T: AsRef<str>> Splice<'a, PreEscaped<T>> {
+        pub fn __maud_render_url<O: Output>(&self, output: &mut O) {
+            output.push_str((self.0).0.as_ref());
+        }
+
+        pub fn __maud_render_js<O: Output>(&self, output: &mut O) {
+            output.push_str((self.0).0.as_ref());
+        }
+
+        pub fn __maud_render_css<O: Output>(&self, output: &mut O) {
+            output.push_str((self.0).0.as_ref());
+        }
+    }
+
+    impl<'a, 'b, T: AsRef<str>> Splice<'a, &'b PreEscaped<T>> {
+        pub fn __maud_render_url<O: Output>(&self, output: &mut O) {
+            output.push_str((self.0).0.as_ref());
+        }
+
+        pub fn __maud_render_js<O: Output>(&self, output: &mut O) {
+            output.push_str((self.0).0.as_ref());
+        }
+
+        pub fn __maud_render_css<O: Output>(&self, output: &mut O) {
+            output.push_str((self.0).0.as_ref());
+        }
+    }
+
+    impl<'a, T: AsRef<str>> Splice<'a, TrustedUrl<T>> {
+        pub fn __maud_render_url<O: Output>(&self, output: &mut O) {
+            output.render(self.0);
+        }
+    }
+
+    impl<'a, 'b, T: AsRef<str>> Splice<'a, &'b TrustedUrl<T>> {
+        pub fn __maud_render_url<O: Output>(&self, output: &mut O) {
+            output.render(self.0);
+        }
+    }
+
+    /// Checks the element name in a `@tag` expression.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is not a valid element name, or if it's `script`
+    /// or `style`. The splices in those elements must be escaped as
+    /// JavaScript or CSS, but which elements a `@tag` makes is only known at
+    /// runtime.
+    pub fn tag_name<T: AsRef<str>>(name: T) -> TagName<T> {
+        if !is_valid_element_name(name.as_ref()) {
+            panic!("invalid element name: {:?}", name.as_ref());
+        }
+        if name.as_ref().eq_ignore_ascii_case("script") || name.as_ref().eq_ignore_ascii_case("style") {
+            panic!("@tag can't make a {:?} element; write it out instead", name.as_ref());
+        }
+        TagName(name)
+    }
+
+    /// The most that `reserve_for` will reserve up front, in bytes.
+    const MAX_LOOP_RESERVE: usize = 64 * 1024;
+
+    /// Converts the iterator of a `@for` loop, reserving enough space for
+    /// each iteration if the iterator knows its exact length.
+    ///
+    /// Since the loop may exit early with `@break`, the amount reserved is
+    /// capped at `MAX_LOOP_RESERVE`.
+    pub fn reserve_for<I: IntoIterator, O: Output>(
+        iter: I,
+        output: &mut O,
+        body_size_hint: usize,
+    ) -> I::IntoIter {
+        let iter = iter.into_iter();
+        if let (len, Some(upper)) = iter.size_hint() {
+            if len == upper {
+                let additional = len.checked_mul(body_size_hint).unwrap_or(MAX_LOOP_RESERVE);
+                output.reserve(cmp::min(additional, MAX_LOOP_RESERVE));
+            }
+        }
+        iter
+    }
+
+    /// Renders a splice in a URL attribute, replacing URLs that could run a
+    /// script.
+    pub fn render_url<T: Render + ?Sized, O: Output>(value: &T, output: &mut O) {
+        let mut buffer = String::new();
+        value.render_to(&mut buffer);
+        push_url(&buffer, output);
+    }
+
+    /// Writes the escaped value of a URL attribute, replacing it if it
+    /// could run a script.
+    pub fn push_url<O: Output>(url: &str, output: &mut O) {
+        if is_unsafe_url(&unescape(url)) {
+            output.push_str(UNSAFE_URL_REPLACEMENT);
+        } else {
+            output.push_str(url);
+        }
+    }
+
+    /// Renders a splice in an event handler attribute or `script` element,
+    /// escaped for use in a JavaScript string.
+    pub fn render_js<T: Render + ?Sized, O: Output>(value: &T, output: &mut O) {
+        let mut buffer = String::new();
+        value.render_to(&mut buffer);
+        let mut escaped = String::with_capacity(buffer.len());
+        for c in unescape(&buffer).chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                // Quotes and HTML special characters, so that the value can't
+                // close the string, the attribute, or the `script` element
+                '"' | '\'' | '`' | '&' | '<' | '>' | '=' | '/' |
+                '\u{0}' ... '\u{1f}' | '\u{7f}' | '\u{2028}' | '\u{2029}' => {
+                    let _ = write!(escaped, "\\u{:04x}", c as u32);
+                },
+                c => escaped.push(c),
+            }
+        }
+        output.push_str(&escaped);
+    }
+
+    /// Renders a splice in a `style` attribute or element, escaped for use
+    /// in CSS.
+    pub fn render_css<T: Render + ?Sized, O: Output>(value: &T, output: &mut O) {
+        let mut buffer = String::new();
+        value.render_to(&mut buffer);
+        let mut escaped = String::with_capacity(buffer.len());
+        for c in unescape(&buffer).chars() {
+            match c {
+                'a' ... 'z' | 'A' ... 'Z' | '0' ... '9' |
+                ' ' | '-' | '_' | '.' | ',' | '#' | '%' => escaped.push(c),
+                c if c > '\u{7f}' => escaped.push(c),
+                // The trailing space ends the escape sequence
+                c => { let _ = write!(escaped, "\\{:x} ", c as u32); },
+            }
+        }
+        output.push_str(&escaped);
+    }
+
+    /// What a URL with a `javascript:` or `data:` scheme is replaced with.
+    const UNSAFE_URL_REPLACEMENT: &str = "about:invalid#maud";
+
+    /// Returns whether the URL has a scheme that could run a script.
+    fn is_unsafe_url(url: &str) -> bool {
+        // Browsers ignore leading control characters and spaces, as well as
+        // tabs and newlines anywhere in the URL
+        let url: String = url.chars()
+            .skip_while(|&c| c <= ' ')
+            .filter(|&c| c != '\t' && c != '\n' && c != '\r')
+            .collect();
+        match url.find(':') {
+            Some(i) => {
+                let scheme = url[..i].to_ascii_lowercase();
+                scheme == "javascript" || scheme == "vbscript" || scheme == "data"
+            },
+            // No scheme, so it's a relative URL
+            None => false,
+        }
+    }
+
+    /// Undoes the escaping done by `Escaper`.
+    fn unescape(s: &str) -> String {
+        s.replace("&quot;", "\"")
+            .replace("&#39;", "'")
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&")
+    }
+
+    fn is_url_attribute(name: &str) -> bool {
+        match &*name.to_ascii_lowercase() {
+            "href" | "src" | "action" | "formaction" | "xlink:href" => true,
+            _ => false,
+        }
+    }
+
+    /// The length of the output at an `html!` call site, as remembered by
+    /// the `learn-capacity` feature.
+    pub struct Capacity(AtomicUsize);
+
+    pub const CAPACITY_INIT: Capacity = Capacity(ATOMIC_USIZE_INIT);
+
+    impl Capacity {
+        pub fn get(&self, size_hint: usize) -> usize {
+            cmp::max(size_hint, self.0.load(Ordering::Relaxed))
+        }
+
+        pub fn update(&self, len: usize) {
+            if len > self.0.load(Ordering::Relaxed) {
+                self.0.store(len, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Writes what goes before a toggled class name: ` class="` if no other
+    /// names have been written yet, or a space otherwise.
+    pub fn class_separator<O: Output>(output: &mut O, is_empty: &mut bool) {
+        if *is_empty {
+            output.push_str(" class=\"");
+            *is_empty = false;
+        } else {
+            output.push_str(" ");
+        }
+    }
+
+    /// The `class` and `id` values from the attribute spreads on an element,
+    /// which are written along with the ones from the template once the
+    /// other attributes are done.
+    #[derive(Default)]
+    pub struct SpreadAttributes {
+        class: String,
+        id: Option<String>,
+    }
+
+    impl SpreadAttributes {
+        pub fn new() -> SpreadAttributes {
+            SpreadAttributes::default()
+        }
+    }
+
+    /// Renders an attribute spread, as in `input ..(attrs)`.
+    ///
+    /// Attributes whose names are not valid are skipped, since the names may
+    /// come from user input. The `class` and `id` attributes are set aside in
+    /// `spread`, to be written by `push_spread_class` and `push_spread_id`.
+    pub fn render_attributes<I, K, V, O>(attrs: I, output: &mut O, spread: &mut SpreadAttributes) where
+        I: IntoIterator<Item=(K, V)>,
+        K: AsRef<str>,
+        V: AttributeValue,
+        O: Output,
+    {
+        render_attributes_impl(attrs, output, spread, false);
+    }
+
+    /// Like `render_attributes`, but used by `xml!`. Empty attributes are
+    /// written with their name as their value, as in `checked="checked"`.
+    pub fn render_xml_attributes<I, K, V, O>(attrs: I, output: &mut O, spread: &mut SpreadAttributes) where
+        I: IntoIterator<Item=(K, V)>,
+        K: AsRef<str>,
+        V: AttributeValue,
+        O: Output,
+    {
+        render_attributes_impl(attrs, output, spread, true);
+    }
+
+    fn render_attributes_impl<I, K, V, O>(attrs: I, output: &mut O, spread: &mut SpreadAttributes, xml: bool) where
+        I: IntoIterator<Item=(K, V)>,
+        K: AsRef<str>,
+        V: AttributeValue,
+        O: Output,
+    {
+        // Attribute names are case-insensitive in HTML, but not in XML
+        let is_named = |name: &str, expected: &str| if xml {
+            name == expected
+        } else {
+            name.eq_ignore_ascii_case(expected)
+        };
+        let mut buffer = String::new();
+        let mut value = String::new();
+        for (name, attr_value) in attrs {
+            let name = name.as_ref();
+            if !is_valid_attribute_name(name) {
+                continue;
+            }
+            value.clear();
+            let kind = attr_value.render_value_to(&mut value);
+            if is_named(name, "class") {
+                if kind == AttributeKind::Value && !value.is_empty() {
+                    if !spread.class.is_empty() {
+                        spread.class.push(' ');
+                    }
+                    spread.class.push_str(&value);
+                }
+                continue;
+            }
+            if is_named(name, "id") {
+                if kind == AttributeKind::Value {
+                    spread.id = Some(value.clone());
+                }
+                continue;
+            }
+            match kind {
+                AttributeKind::Omitted => {},
+                AttributeKind::Empty => {
+                    buffer.push(' ');
+                    buffer.push_str(name);
+                    if xml {
+                        buffer.push_str("=\"");
+                        buffer.push_str(name);
+                        buffer.push('"');
+                    }
+                },
+                AttributeKind::Value => {
+                    buffer.push(' ');
+                    buffer.push_str(name);
+                    buffer.push_str("=\"");
+                    if is_url_attribute(name) {
+                        push_url(&value, &mut buffer);
+                    } else {
+                        buffer.push_str(&value);
+                    }
+                    buffer.push('"');
+                },
+            }
+        }
+        output.push_str(&buffer);
+    }
+
+    /// Writes the classes from the attribute spreads on an element, after
+    /// the ones from the template (see `class_separator`).
+    pub fn push_spread_class<O: Output>(spread: &SpreadAttributes, output: &mut O, is_empty: &mut bool) {
+        if !spread.class.is_empty() {
+            class_separator(output, is_empty);
+            output.push_str(&spread.class);
+        }
+    }
+
+    /// Writes the ID from the attribute spreads on an element. This is only
+    /// called if the template doesn't give the element an ID.
+    pub fn push_spread_id<O: Output>(spread: &SpreadAttributes, output: &mut O) {
+        if let Some(ref id) = spread.id {
+            output.push_str(" id=\"");
+            output.push_str(id);
+            output.push_str("\"");
+        }
+    }
+}
+
+#[cfg(feature = "iron")]
+mod iron_support {
+    use std::io;
+    use iron::headers::ContentType;
+    use iron::modifier::{Modifier, Set};
+    use iron::modifiers::Header;
+    use iron::response::{Response, WriteBody};
+    use macro_private::ChunkedSink;
+    use {PreEscaped, Streaming};
+
+    impl Modifier<Response> for PreEscaped<String> {
+        fn modify(self, response: &mut Response) {
+            response
+                .set_mut(Header(ContentType::html()))
+                .set_mut(Box::new(self) as Box<WriteBody>);
+        }
+    }
+
+    impl WriteBody for PreEscaped<String> {
+        fn write_body(&mut self, body: &mut io::Write) -> io::Result<()> {
+            self.0.write_body(body)
+        }
+    }
+
+    impl<F> Modifier<Response> for Streaming<F> where
+        F: FnOnce(&mut ChunkedSink) + Send + 'static,
+    {
+        fn modify(self, response: &mut Response) {
+            response
+                .set_mut(Header(ContentType::html()))
+                .set_mut(Box::new(self) as Box<WriteBody>);
+        }
+    }
+
+    impl<F> WriteBody for Streaming<F> where
+        F: FnOnce(&mut ChunkedSink) + Send,
+    {
+        fn write_body(&mut self, body: &mut io::Write) -> io::Result<()> {
+            Streaming { render: self.render.take() }.write_to(body)
+        }
+    }
+}
+
+#[cfg(feature = "rocket")]
 mod rocket_support {
     use rocket::http::{ContentType, Status};
     use rocket::request::Request;
     use rocket::response::{Responder, Response};
-    use std::io::Cursor;
-    use PreEscaped;
+    use std::io::{self, Cursor, Read};
+    use macro_private::ChunkedSink;
+    use {Chunks, PreEscaped, Streaming};
 
     impl Responder<'static> for PreEscaped<String> {
         fn respond_to(self, _: &Request) -> Result<Response<'static>, Status> {
@@ -188,4 +1734,119 @@ mod rocket_support {
                 .ok()
         }
     }
+
+    /// The template is rendered on a new thread, and each chunk is sent as
+    /// soon as it's done, using chunked transfer encoding.
+    impl<F> Responder<'static> for Streaming<F> where
+        F: FnOnce(&mut ChunkedSink) + Send + 'static,
+    {
+        fn respond_to(self, _: &Request) -> Result<Response<'static>, Status> {
+            let reader = ChunkReader {
+                chunks: self.into_iter(),
+                current: Cursor::new(String::new()),
+            };
+            Response::build()
+                .header(ContentType::HTML)
+                .streamed_body(reader)
+                .ok()
+        }
+    }
+
+    struct ChunkReader {
+        chunks: Chunks,
+        current: Cursor<String>,
+    }
+
+    impl Read for ChunkReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            loop {
+                let n = self.current.read(buf)?;
+                if n > 0 || buf.is_empty() {
+                    return Ok(n);
+                }
+                match self.chunks.next() {
+                    Some(chunk) => self.current = Cursor::new(chunk),
+                    None => return Ok(0),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "futures")]
+mod futures_support {
+    use futures::{Async, Poll, Stream};
+    use {ChunkStream, join_render_thread};
+
+    impl Stream for ChunkStream {
+        type Item = String;
+        type Error = ();
+
+        fn poll(&mut self) -> Poll<Option<String>, ()> {
+            let result = self.receiver.poll();
+            if let Ok(Async::Ready(None)) = result {
+                // The sender has been dropped, so the thread is about to end
+                join_render_thread(&mut self.thread);
+            }
+            result
+        }
+    }
+}
+
+/// Support for [Serde](https://serde.rs).
+///
+/// `PreEscaped` is serialized as its raw string, and `Escaped` as its inner
+/// value. Only `Escaped` can be deserialized by default, since a string read
+/// into `PreEscaped` would be rendered without escaping it; use the
+/// `trusted` module for sources which you trust as much as your own
+/// templates.
+#[cfg(feature = "serde")]
+pub mod serde {
+    use std::fmt;
+    use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+    use {Escaped, PreEscaped};
+
+    /// Serializes the markup as a string, as is.
+    impl<T: AsRef<str>> Serialize for PreEscaped<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(self.as_str())
+        }
+    }
+
+    impl<T: fmt::Display + Serialize> Serialize for Escaped<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(serializer)
+        }
+    }
+
+    impl<'de, T: fmt::Display + Deserialize<'de>> Deserialize<'de> for Escaped<T> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Escaped<T>, D::Error> {
+            T::deserialize(deserializer).map(Escaped)
+        }
+    }
+
+    /// Deserializes a string as markup, without escaping it.
+    ///
+    /// Only use this with a source which you trust as much as your own
+    /// templates, such as a cache of rendered markup:
+    ///
+    /// ```rust,ignore
+    /// #[derive(Deserialize)]
+    /// struct CachedPage {
+    ///     #[serde(with = "maud::serde::trusted")]
+    ///     body: Markup,
+    /// }
+    /// ```
+    pub mod trusted {
+        use serde_crate::{Deserialize, Deserializer, Serialize, Serializer};
+        use PreEscaped;
+
+        pub fn serialize<T: AsRef<str>, S: Serializer>(markup: &PreEscaped<T>, serializer: S) -> Result<S::Ok, S::Error> {
+            markup.serialize(serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PreEscaped<String>, D::Error> {
+            String::deserialize(deserializer).map(PreEscaped)
+        }
+    }
 }
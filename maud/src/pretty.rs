@@ -0,0 +1,209 @@
+//! Pretty-printing for rendered markup.
+//!
+//! Whitespace is only added between block-level elements, where browsers
+//! ignore it anyway. Everything else, including the contents of inline and
+//! whitespace-sensitive elements, is copied verbatim.
+
+const INDENT: &str = "  ";
+
+/// Elements which are laid out as blocks by default, or not displayed at
+/// all, so that whitespace around them is insignificant.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "base", "blockquote", "body", "dd",
+    "details", "dialog", "div", "dl", "dt", "fieldset", "figcaption", "figure",
+    "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "head", "header",
+    "hgroup", "hr", "html", "li", "link", "main", "meta", "nav", "ol", "p",
+    "pre", "script", "section", "style", "summary", "table", "tbody", "td",
+    "tfoot", "th", "thead", "title", "tr", "ul",
+];
+
+/// Elements whose contents must never be reformatted.
+const PRESERVE_ELEMENTS: &[&str] = &["pre", "textarea", "script", "style", "title"];
+
+/// Elements whose contents are raw text, rather than markup.
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style", "textarea", "title"];
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta",
+    "param", "source", "track", "wbr",
+];
+
+struct Node<'a> {
+    /// The markup for the whole node.
+    source: &'a str,
+    kind: Kind<'a>,
+}
+
+enum Kind<'a> {
+    Element {
+        name: String,
+        start_tag: &'a str,
+        end_tag: &'a str,
+        children: Vec<Node<'a>>,
+    },
+    Text,
+    /// A comment, doctype, or processing instruction.
+    Other,
+}
+
+/// Indents the block-level elements in the given markup.
+///
+/// If the markup can't be parsed (because of a missing end tag, say), then
+/// it's returned unchanged.
+pub fn pretty(input: &str) -> String {
+    let mut pos = 0;
+    let nodes = match parse_nodes(input, &mut pos, None) {
+        Some(nodes) => nodes,
+        None => return input.to_string(),
+    };
+    if !is_block_sequence(&nodes) {
+        return input.to_string();
+    }
+    let mut output = String::with_capacity(input.len() * 5 / 4);
+    for (i, node) in nodes.iter().filter(|node| !is_whitespace(node)).enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        write_node(node, 0, &mut output);
+    }
+    output
+}
+
+fn write_node(node: &Node, depth: usize, output: &mut String) {
+    match node.kind {
+        Kind::Element { ref name, start_tag, end_tag, ref children }
+        if !contains(PRESERVE_ELEMENTS, name) && !children.is_empty()
+            && is_block_sequence(children) => {
+            output.push_str(start_tag);
+            for child in children.iter().filter(|child| !is_whitespace(child)) {
+                newline(depth + 1, output);
+                write_node(child, depth + 1, output);
+            }
+            newline(depth, output);
+            output.push_str(end_tag);
+        },
+        _ => output.push_str(node.source),
+    }
+}
+
+fn newline(depth: usize, output: &mut String) {
+    output.push('\n');
+    for _ in 0..depth {
+        output.push_str(INDENT);
+    }
+}
+
+/// Returns whether the nodes can be put on separate lines: that is, whether
+/// they're all blocks, comments, or whitespace.
+fn is_block_sequence(nodes: &[Node]) -> bool {
+    nodes.iter().all(|node| match node.kind {
+        Kind::Element { ref name, .. } => contains(BLOCK_ELEMENTS, name),
+        Kind::Text => is_whitespace(node),
+        Kind::Other => true,
+    })
+}
+
+fn is_whitespace(node: &Node) -> bool {
+    match node.kind {
+        Kind::Text => node.source.chars().all(is_whitespace_char),
+        _ => false,
+    }
+}
+
+fn is_whitespace_char(c: char) -> bool {
+    match c {
+        ' ' | '\t' | '\n' | '\r' | '\x0C' => true,
+        _ => false,
+    }
+}
+
+fn contains(names: &[&str], name: &str) -> bool {
+    names.iter().any(|&candidate| candidate == name)
+}
+
+/// Parses a sequence of nodes, stopping at the end tag of `parent`.
+fn parse_nodes<'a>(input: &'a str, pos: &mut usize, parent: Option<&str>) -> Option<Vec<Node<'a>>> {
+    let mut nodes = Vec::new();
+    loop {
+        let rest = &input[*pos..];
+        if rest.is_empty() {
+            // A missing end tag
+            return if parent.is_none() { Some(nodes) } else { None };
+        } else if rest.starts_with("</") {
+            // Leave the end tag for the caller
+            let name = tag_name(&rest[2..]);
+            return if parent == Some(name) { Some(nodes) } else { None };
+        } else if rest.starts_with("<!--") {
+            let len = rest.find("-->")? + 3;
+            nodes.push(Node { source: &rest[..len], kind: Kind::Other });
+            *pos += len;
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            let len = rest.find('>')? + 1;
+            nodes.push(Node { source: &rest[..len], kind: Kind::Other });
+            *pos += len;
+        } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| match c {
+            'a' ... 'z' | 'A' ... 'Z' => true,
+            _ => false,
+        }) {
+            nodes.push(parse_element(input, pos)?);
+        } else {
+            // Text runs until the next tag, and includes at least the first
+            // character (which may be a stray `<`)
+            let first_len = rest.chars().next().map_or(0, char::len_utf8);
+            let len = rest[first_len..].find('<').map_or(rest.len(), |i| i + first_len);
+            nodes.push(Node { source: &rest[..len], kind: Kind::Text });
+            *pos += len;
+        }
+    }
+}
+
+fn parse_element<'a>(input: &'a str, pos: &mut usize) -> Option<Node<'a>> {
+    let start = *pos;
+    let name = tag_name(&input[start + 1..]);
+    let start_tag_len = start_tag_len(&input[start..])?;
+    let start_tag = &input[start..start + start_tag_len];
+    *pos += start_tag_len;
+    let lower_name = name.to_ascii_lowercase();
+    let mut children = Vec::new();
+    let mut end_tag = "";
+    let is_void = contains(VOID_ELEMENTS, &lower_name) || start_tag.ends_with("/>");
+    if !is_void {
+        if contains(RAW_TEXT_ELEMENTS, &lower_name) {
+            let len = input[*pos..].find(&format!("</{}", name))?;
+            if len > 0 {
+                children.push(Node { source: &input[*pos..*pos + len], kind: Kind::Text });
+            }
+            *pos += len;
+        } else {
+            children = parse_nodes(input, pos, Some(name))?;
+        }
+        let end_tag_len = input[*pos..].find('>')? + 1;
+        end_tag = &input[*pos..*pos + end_tag_len];
+        *pos += end_tag_len;
+    }
+    Some(Node {
+        source: &input[start..*pos],
+        kind: Kind::Element { name: lower_name, start_tag, end_tag, children },
+    })
+}
+
+/// Returns the tag name at the start of the given string.
+fn tag_name(s: &str) -> &str {
+    let len = s.find(|c: char| is_whitespace_char(c) || c == '/' || c == '>').unwrap_or(s.len());
+    &s[..len]
+}
+
+/// Returns the length of the start tag at the start of the given string,
+/// skipping over any `>` in quoted attribute values.
+fn start_tag_len(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, b) in s.bytes().enumerate() {
+        match (quote, b) {
+            (None, b'"') | (None, b'\'') => quote = Some(b),
+            (Some(q), _) if q == b => quote = None,
+            (None, b'>') => return Some(i + 1),
+            _ => {},
+        }
+    }
+    None
+}
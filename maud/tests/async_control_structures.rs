@@ -0,0 +1,224 @@
+#![cfg(feature = "futures-await")]
+
+#![feature(conservative_impl_trait)]
+#![feature(generators)]
+#![feature(plugin)]
+#![feature(proc_macro)]
+
+#![plugin(maud_lints)]
+
+#[macro_use] extern crate futures_await as futures;
+extern crate maud;
+
+use futures::prelude::*;
+use futures::{future, stream};
+use maud::{Markup, html_async};
+
+fn render<F: Future<Item=Markup, Error=()>>(markup: F) -> String {
+    markup.wait().unwrap().into_string()
+}
+
+#[test]
+fn await_splice() {
+    let name = future::ok::<_, ()>("Pinkie Pie");
+    let s = render(html_async! {
+        p { "Hello, " (await!(name)?) "!" }
+    });
+    assert_eq!(s, "<p>Hello, Pinkie Pie!</p>");
+}
+
+#[test]
+fn await_error() {
+    let name = future::err::<&str, _>("no pony");
+    let result = html_async!(p (await!(name)?)).wait();
+    assert_eq!(result, Err("no pony"));
+}
+
+#[test]
+fn if_expr() {
+    for (number, &name) in (1..4).zip(["one", "two", "three"].iter()) {
+        let s = render(html_async! {
+            @if number == 1 {
+                "one"
+            } @else if number == 2 {
+                "two"
+            } @else if number == 3 {
+                "three"
+            } @else {
+                "oh noes"
+            }
+        });
+        assert_eq!(s, name);
+    }
+}
+
+#[test]
+fn if_let() {
+    for &(input, output) in &[(Some("yay"), "yay"), (None, "oh noes")] {
+        let s = render(html_async! {
+            @if let Some(value) = input {
+                (value)
+            } @else {
+                "oh noes"
+            }
+        });
+        assert_eq!(s, output);
+    }
+}
+
+#[test]
+fn while_let_expr() {
+    let mut numbers = (0..3).into_iter();
+    #[cfg_attr(feature = "cargo-clippy", allow(while_let_on_iterator))]
+    let s = render(html_async! {
+        ul @while let Some(n) = numbers.next() {
+            li (n)
+        }
+    });
+    assert_eq!(s, "<ul><li>0</li><li>1</li><li>2</li></ul>");
+}
+
+#[test]
+fn for_expr() {
+    let ponies = ["Apple Bloom", "Scootaloo", "Sweetie Belle"];
+    let s = render(html_async! {
+        ul @for pony in &ponies {
+            li (pony)
+        }
+    });
+    assert_eq!(s, concat!(
+            "<ul>",
+            "<li>Apple Bloom</li>",
+            "<li>Scootaloo</li>",
+            "<li>Sweetie Belle</li>",
+            "</ul>"));
+}
+
+#[test]
+fn for_empty() {
+    fn test(ponies: &'static [&'static str]) -> String {
+        render(html_async! {
+            ul @for pony in ponies {
+                li (pony)
+            } @empty {
+                li.empty "No ponies :("
+            }
+        })
+    }
+    assert_eq!(test(&["Apple Bloom", "Scootaloo"]), concat!(
+            "<ul>",
+            "<li>Apple Bloom</li>",
+            "<li>Scootaloo</li>",
+            "</ul>"));
+    assert_eq!(test(&[]), r#"<ul><li class="empty">No ponies :(</li></ul>"#);
+}
+
+#[test]
+fn for_continue() {
+    let s = render(html_async! {
+        @for i in 0..6 {
+            @if i % 2 == 0 {
+                @continue
+            }
+            (i)
+        }
+    });
+    assert_eq!(s, "135");
+}
+
+#[test]
+fn labeled_break() {
+    let rows = [[1, 2, 3], [4, 0, 6], [7, 8, 9]];
+    let s = render(html_async! {
+        @'rows: for row in &rows {
+            @for &cell in row {
+                @if cell == 0 {
+                    @break 'rows;
+                }
+                (cell)
+            }
+        }
+    });
+    assert_eq!(s, "1234");
+}
+
+#[test]
+fn for_await() {
+    let ponies = stream::iter_ok::<_, ()>(vec!["Apple Bloom", "Scootaloo", "Sweetie Belle"]);
+    let s = render(html_async! {
+        ul @for await pony in ponies {
+            li (pony)
+        }
+    });
+    assert_eq!(s, concat!(
+            "<ul>",
+            "<li>Apple Bloom</li>",
+            "<li>Scootaloo</li>",
+            "<li>Sweetie Belle</li>",
+            "</ul>"));
+}
+
+#[test]
+fn for_await_break() {
+    let numbers = stream::iter_ok::<_, ()>(0..);
+    let s = render(html_async! {
+        @for await n in numbers {
+            @if n == 3 {
+                @break;
+            }
+            (n)
+        }
+    });
+    assert_eq!(s, "012");
+}
+
+#[test]
+fn for_await_labeled() {
+    let rows = stream::iter_ok::<_, ()>(vec![[1, 0, 3], [4, 5, 6]]);
+    let s = render(html_async! {
+        @'rows: for await row in rows {
+            "["
+            @for &cell in &row {
+                @if cell == 0 {
+                    @continue 'rows;
+                }
+                (cell)
+            }
+            "]"
+        }
+    });
+    assert_eq!(s, "[1[456]");
+}
+
+#[test]
+fn for_await_error() {
+    let numbers = stream::iter_result(vec![Ok(1), Err("oops"), Ok(3)]);
+    let result = html_async!(@for await n in numbers { (n) }).wait();
+    assert_eq!(result, Err("oops"));
+}
+
+#[test]
+fn match_expr() {
+    for &(input, output) in &[(Some("yay"), "<div>yay</div>"), (None, "oh noes")] {
+        let s = render(html_async! {
+            @match input {
+                Some(value) => {
+                    div (value)
+                },
+                None => {
+                    "oh noes"
+                },
+            }
+        });
+        assert_eq!(s, output);
+    }
+}
+
+#[test]
+fn let_expr() {
+    let s = render(html_async! {
+        @let x = 42;
+        "I have " (x) " cupcakes!"
+    });
+    assert_eq!(s, "I have 42 cupcakes!");
+}
@@ -67,6 +67,54 @@ fn empty_elements_slash() {
     assert_eq!(s, "pinkie<br>pie");
 }
 
+#[test]
+fn tag_expr() {
+    for level in 1..3 {
+        let s = html!(@tag (format!("h{}", level)) .title { "Hello!" }).into_string();
+        assert_eq!(s, format!(r#"<h{0} class="title">Hello!</h{0}>"#, level));
+    }
+}
+
+#[test]
+fn tag_expr_void() {
+    let tag = "br";
+    let s = html!("pinkie" @tag (tag); "pie").into_string();
+    assert_eq!(s, "pinkie<br>pie");
+}
+
+#[test]
+fn tag_expr_nested() {
+    let s = html!(@tag ("ul") { @tag ("li") "one" }).into_string();
+    assert_eq!(s, "<ul><li>one</li></ul>");
+}
+
+#[test]
+fn tag_expr_tag_name() {
+    use maud::TagName;
+    let name = TagName::new("h2").unwrap();
+    let s = html!(@tag (name) "Hello!").into_string();
+    assert_eq!(s, "<h2>Hello!</h2>");
+}
+
+#[test]
+fn tag_literal() {
+    let s = html!(@tag "my-el-2" .card { "Hi" } @tag "x-icon";).into_string();
+    assert_eq!(s, r#"<my-el-2 class="card">Hi</my-el-2><x-icon>"#);
+}
+
+#[test]
+#[should_panic]
+fn tag_expr_invalid() {
+    let _ = html!(@tag ("script><script") {});
+}
+
+#[test]
+#[should_panic]
+fn tag_expr_script() {
+    let name = "SCRIPT";
+    let _ = html!(@tag (name) { (name) });
+}
+
 #[test]
 fn simple_attributes() {
     let s = html! {
@@ -109,6 +157,24 @@ fn toggle_empty_attributes_braces() {
     assert_eq!(s, r#"<input checked>"#);
 }
 
+#[test]
+fn optional_attributes() {
+    fn test(title: Option<&str>) -> Markup {
+        html!(p title=[title] "Hi")
+    }
+    assert_eq!(test(Some("<greeting>")).into_string(), r#"<p title="&lt;greeting&gt;">Hi</p>"#);
+    assert_eq!(test(None).into_string(), "<p>Hi</p>");
+}
+
+#[test]
+fn optional_attributes_not_moved() {
+    let value = Some(String::from("pinkie"));
+    let s = html!(input value=[value];).into_string();
+    assert_eq!(s, r#"<input value="pinkie">"#);
+    // Make sure the `html!` call didn't move it
+    let _value = value;
+}
+
 #[test]
 fn colons_in_names() {
     let s = html!(pon-pon:controls-alpha a on:click="yay()" "Yay!").into_string();
@@ -130,6 +196,24 @@ fn hyphens_in_attribute_names() {
     assert_eq!(s, r#"<this sentence-is="false" of-course></this>"#);
 }
 
+#[test]
+fn numbers_in_names() {
+    let s = html!(my-el-2 data-2col="yes" {}).into_string();
+    assert_eq!(s, r#"<my-el-2 data-2col="yes"></my-el-2>"#);
+}
+
+#[test]
+fn string_literal_attribute_names() {
+    let s = html! {
+        button "@click"="open = !open" ":class"="{ active: open }" "x-on:click.prevent"? {
+            "Toggle"
+        }
+    }.into_string();
+    assert_eq!(s, concat!(
+            r#"<button @click="open = !open" :class="{ active: open }" x-on:click.prevent>"#,
+            r#"Toggle</button>"#));
+}
+
 #[test]
 fn class_shorthand() {
     let s = html!(p { "Hi, " span.name { "Lyra" } "!" }).into_string();
@@ -160,9 +244,9 @@ fn toggle_classes() {
         html!(p.cupcake[is_cupcake].muffin[is_muffin] "Testing!")
     }
     assert_eq!(test(true, true).into_string(), r#"<p class="cupcake muffin">Testing!</p>"#);
-    assert_eq!(test(false, true).into_string(), r#"<p class=" muffin">Testing!</p>"#);
+    assert_eq!(test(false, true).into_string(), r#"<p class="muffin">Testing!</p>"#);
     assert_eq!(test(true, false).into_string(), r#"<p class="cupcake">Testing!</p>"#);
-    assert_eq!(test(false, false).into_string(), r#"<p class="">Testing!</p>"#);
+    assert_eq!(test(false, false).into_string(), r#"<p>Testing!</p>"#);
 }
 
 #[test]
@@ -181,14 +265,275 @@ fn mixed_classes() {
     assert_eq!(test(false).into_string(), r#"<p class="cupcake lamington">Testing!</p>"#);
 }
 
+#[test]
+fn spliced_classes() {
+    let theme = "dark";
+    let s = html!(div.(theme) {}).into_string();
+    assert_eq!(s, r#"<div class="dark"></div>"#);
+}
+
+#[test]
+fn spliced_classes_mixed() {
+    fn test(n: u32, is_active: bool) -> Markup {
+        html!(li.item.(format!("col-{}", n)).active[is_active] "Testing!")
+    }
+    assert_eq!(test(3, true).into_string(), r#"<li class="item col-3 active">Testing!</li>"#);
+    assert_eq!(test(4, false).into_string(), r#"<li class="item col-4">Testing!</li>"#);
+}
+
+#[test]
+fn spliced_classes_toggled() {
+    let s = html!(p.("cupcake")[true].("muffin")[false] "Testing!").into_string();
+    assert_eq!(s, r#"<p class="cupcake">Testing!</p>"#);
+}
+
+#[test]
+fn spliced_classes_escaping() {
+    let s = html!(p.("\"><script>") {}).into_string();
+    assert_eq!(s, r#"<p class="&quot;&gt;&lt;script&gt;"></p>"#);
+}
+
 #[test]
 fn ids_shorthand() {
     let s = html!(p { "Hi, " span#thing { "Lyra" } "!" }).into_string();
     assert_eq!(s, r#"<p>Hi, <span id="thing">Lyra</span>!</p>"#);
 }
 
+#[test]
+fn spliced_ids() {
+    let slug = "getting-started";
+    let s = html!(section#(slug) { "Hi" }).into_string();
+    assert_eq!(s, r#"<section id="getting-started">Hi</section>"#);
+}
+
+#[test]
+fn class_shorthand_and_attribute() {
+    let extra = "featured";
+    let s = html!(div.card class=(extra) {}).into_string();
+    assert_eq!(s, r#"<div class="card featured"></div>"#);
+}
+
+#[test]
+fn class_attribute_and_toggled_classes() {
+    fn test(is_cupcake: bool) -> Markup {
+        html!(p.cupcake[is_cupcake] class="muffin" "Testing!")
+    }
+    assert_eq!(test(true).into_string(), r#"<p class="muffin cupcake">Testing!</p>"#);
+    assert_eq!(test(false).into_string(), r#"<p class="muffin">Testing!</p>"#);
+}
+
+#[test]
+fn optional_class_attribute() {
+    fn test(extra: Option<&str>) -> Markup {
+        html!(p.cupcake class=[extra] "Testing!")
+    }
+    assert_eq!(test(Some("muffin")).into_string(), r#"<p class="cupcake muffin">Testing!</p>"#);
+    assert_eq!(test(None).into_string(), r#"<p class="cupcake">Testing!</p>"#);
+}
+
+#[test]
+fn class_attribute_with_control_structure() {
+    let s = html!(p.cupcake class=@if true { "muffin" } "Testing!").into_string();
+    assert_eq!(s, r#"<p class="cupcake muffin">Testing!</p>"#);
+}
+
+#[test]
+fn spliced_id_attribute() {
+    let slug = "getting-started";
+    let s = html!(section.intro id=(slug) { "Hi" }).into_string();
+    assert_eq!(s, r#"<section class="intro" id="getting-started">Hi</section>"#);
+}
+
+#[test]
+fn attribute_spread() {
+    use std::collections::BTreeMap;
+    let mut attrs = BTreeMap::new();
+    attrs.insert("aria-label", "Close <dialog>");
+    attrs.insert("data-id", "42");
+    let s = html!(button.close ..(&attrs) "x").into_string();
+    assert_eq!(s, concat!(
+            r#"<button aria-label="Close &lt;dialog&gt;" data-id="42" class="close">"#,
+            r#"x</button>"#));
+}
+
+#[test]
+fn attribute_spread_optional() {
+    use std::borrow::Cow;
+    let attrs: Vec<(Cow<str>, Option<String>)> = vec![
+        (Cow::Borrowed("title"), Some("Pinkie".to_string())),
+        (Cow::Owned("data-unused".to_string()), None),
+    ];
+    let s = html!(input type="text" ..(attrs) disabled?;).into_string();
+    assert_eq!(s, r#"<input type="text" title="Pinkie" disabled>"#);
+}
+
+#[test]
+fn attribute_spread_invalid_names() {
+    let attrs = vec![
+        ("data-ok", "1"),
+        ("onclick=\"alert(1)\"", "x"),
+        ("data bad", "2"),
+        ("", "3"),
+    ];
+    let s = html!(div ..(attrs) {}).into_string();
+    assert_eq!(s, r#"<div data-ok="1"></div>"#);
+}
+
+#[test]
+fn attribute_spread_class_and_id() {
+    let attrs = vec![("class", "open"), ("id", "dialog"), ("title", "Close")];
+    let s = html!(button.close ..(attrs.clone()) "x").into_string();
+    assert_eq!(s, r#"<button title="Close" class="close open" id="dialog">x</button>"#);
+    let s = html!(button#main ..(attrs) "x").into_string();
+    assert_eq!(s, r#"<button title="Close" class="open" id="main">x</button>"#);
+}
+
+#[test]
+fn attribute_spread_custom_value() {
+    use maud::{AttributeKind, AttributeValue};
+    struct Count(u32);
+    impl AttributeValue for Count {
+        fn render_value_to(&self, buffer: &mut String) -> AttributeKind {
+            if self.0 == 0 {
+                return AttributeKind::Omitted;
+            }
+            buffer.push_str(&self.0.to_string());
+            AttributeKind::Value
+        }
+    }
+    let attrs = vec![("data-count", Count(3)), ("data-none", Count(0))];
+    let s = html!(span ..(attrs) {}).into_string();
+    assert_eq!(s, r#"<span data-count="3"></span>"#);
+}
+
 #[test]
 fn classes_attrs_ids_mixed_up() {
     let s = html!(p { "Hi, " span.name.here lang="en" #thing { "Lyra" } "!" }).into_string();
     assert_eq!(s, "<p>Hi, <span lang=\"en\" class=\"name here\" id=\"thing\">Lyra</span>!</p>");
 }
+
+#[test]
+fn svg() {
+    let s = html! {
+        svg viewBox="0 0 10 10" {
+            defs {
+                linearGradient#fade {
+                    stop offset="0";
+                    stop offset="1" {}
+                }
+            }
+            circle cx="5" cy="5" r="4" fill="url(#fade)";
+        }
+    }.into_string();
+    assert_eq!(s, concat!(
+        r#"<svg viewBox="0 0 10 10" xmlns="http://www.w3.org/2000/svg">"#,
+        r#"<defs><linearGradient id="fade"><stop offset="0"/><stop offset="1"/></linearGradient></defs>"#,
+        r#"<circle cx="5" cy="5" r="4" fill="url(#fade)"/>"#,
+        "</svg>"));
+}
+
+#[test]
+fn svg_explicit_xmlns() {
+    let s = html!(svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" {
+        use xlink:href="#icon";
+    }).into_string();
+    assert_eq!(s, concat!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">"#,
+        r##"<use xlink:href="#icon"/></svg>"##));
+}
+
+#[test]
+fn svg_foreign_object() {
+    let s = html!(svg { foreignObject { p { br; "Hello" } } }).into_string();
+    assert_eq!(s, concat!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg">"#,
+        "<foreignObject><p><br>Hello</p></foreignObject></svg>"));
+}
+
+#[test]
+fn mathml() {
+    let s = html!(math { mfrac { mn "1" mn "2" } mspace width="1em"; }).into_string();
+    assert_eq!(s, concat!(
+        r#"<math xmlns="http://www.w3.org/1998/Math/MathML">"#,
+        r#"<mfrac><mn>1</mn><mn>2</mn></mfrac><mspace width="1em"/></math>"#));
+}
+
+#[test]
+fn mathml_text_integration_point() {
+    let s = html!(math { mtext { b "x" } }).into_string();
+    assert_eq!(s, concat!(
+        r#"<math xmlns="http://www.w3.org/1998/Math/MathML">"#,
+        "<mtext><b>x</b></mtext></math>"));
+}
+
+#[test]
+fn html_integration_points() {
+    let s = html! {
+        svg { title { span "Pinkie" } }
+        math { annotation-xml encoding="text/html" { p "Pie" } }
+    }.into_string();
+    assert_eq!(s, concat!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><title><span>Pinkie</span></title></svg>"#,
+        r#"<math xmlns="http://www.w3.org/1998/Math/MathML">"#,
+        r#"<annotation-xml encoding="text/html"><p>Pie</p></annotation-xml></math>"#));
+}
+
+#[test]
+fn svg_inside_mathml() {
+    let s = html!(math { mrow { svg { circle r="1"; } } }).into_string();
+    assert_eq!(s, concat!(
+        r#"<math xmlns="http://www.w3.org/1998/Math/MathML"><mrow>"#,
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><circle r="1"/></svg></mrow></math>"#));
+}
+
+#[test]
+fn void_elements_outside_svg() {
+    let s = html!(p { br; } svg { path d="M0 0"; } img src="pinkie.png";).into_string();
+    assert_eq!(s, concat!(
+        "<p><br></p>",
+        r#"<svg xmlns="http://www.w3.org/2000/svg"><path d="M0 0"/></svg>"#,
+        r#"<img src="pinkie.png">"#));
+}
+
+#[test]
+fn collapse_whitespace() {
+    let s = html! {
+        @collapse {
+            p { "
+                Hello,
+                world!
+            " }
+            pre { "  keep\n  this  " }
+        }
+        p "  not   collapsed  "
+    }.into_string();
+    assert_eq!(s, concat!(
+        "<p> Hello, world! </p>",
+        "<pre>  keep\n  this  </pre>",
+        "<p>  not   collapsed  </p>"));
+}
+
+#[test]
+fn collapse_whitespace_across_literals() {
+    let s = html!(@collapse { "Pinkie  " "  Pie" }).into_string();
+    assert_eq!(s, "Pinkie Pie");
+}
+
+#[test]
+fn trim_marker() {
+    let s = html!("Hello,  " ~ b "Pinkie" ~ "  !").into_string();
+    assert_eq!(s, "Hello,<b>Pinkie</b>!");
+}
+
+#[test]
+fn whitespace_control_keeps_nbsp() {
+    let s = html!(@collapse { "10\u{a0}\u{a0}km " ~ "\u{a0}away" }).into_string();
+    assert_eq!(s, "10\u{a0}\u{a0}km\u{a0}away");
+}
+
+#[test]
+fn trim_marker_around_splice() {
+    let name = "Pinkie";
+    let s = html!(p { "Hello,\n" ~ (name) ~ "\n!" }).into_string();
+    assert_eq!(s, "<p>Hello,Pinkie!</p>");
+}
@@ -0,0 +1,20 @@
+#![feature(proc_macro)]
+
+extern crate maud;
+
+use maud::html;
+
+fn main() {
+    html!(@if true);  //~ ERROR unexpected end of @if expression
+    html!(@if true {} @else p);  //~ ERROR expected body for @else
+    html!(@while true);  //~ ERROR unexpected end of @while expression
+    html!(@for await x in 0..3 {});  //~ ERROR @for await only works in html_async!
+    html!(@for x in 0..3);  //~ ERROR unexpected end of @for expression
+    html!(@for x in 0..3 {} @empty p);  //~ ERROR expected body for @empty
+    html!(@'a for x in 0..3 {});  //~ ERROR expected `:` after loop label
+    html!(@'a: if true {});  //~ ERROR expected `for` or `while` after loop label
+    html!(@match 1);  //~ ERROR unexpected end of @match expression
+    html!(@match 1 { _ });  //~ ERROR unexpected end of @match pattern
+    html!(@match 1 { _ => "a" });  //~ ERROR unexpected end of @match arm
+    html!(@let x = 1);  //~ ERROR unexpected end of @let expression
+}
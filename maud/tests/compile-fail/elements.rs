@@ -0,0 +1,20 @@
+#![feature(proc_macro)]
+
+extern crate maud;
+
+use maud::html;
+
+fn main() {
+    html!(42);  //~ ERROR expected string
+    html!([1]);  //~ ERROR invalid syntax
+    html!(a title=);  //~ ERROR unexpected end of input
+    html!(a title=b {});  //~ ERROR unexpected element
+    html!(p "a b"="c" {});  //~ ERROR invalid attribute name `a b`
+    html!(p id="a" id="b" {});  //~ ERROR an element can only have one id
+    html!(p #a #b {});  //~ ERROR an element can only have one id
+    html!(p ..a {});  //~ ERROR expected attribute spread `..(expr)`
+    html!(p . {});  //~ ERROR expected identifier
+    html!(svg { Circle; });  //~ ERROR unknown SVG element `Circle`; did you mean `circle`?
+    html!(svg { @tag "Circle" {} });  //~ ERROR unknown SVG element `Circle`
+    html!(svg viewbox="0 0 1 1" {});  //~ ERROR unknown attribute `viewbox`; did you mean `viewBox`?
+}
@@ -0,0 +1,12 @@
+#![feature(proc_macro)]
+
+extern crate maud;
+
+use maud::html_to;
+
+fn main() {
+    let mut s = String::new();
+    html_to!(s p { "Pinkie" }).unwrap();  //~ ERROR expected a writer, followed by a comma and the template
+    html_to!(, p { "Pinkie" }).unwrap();  //~ ERROR expected a writer, followed by a comma and the template
+    html_to!(s, p { "Pinkie" }).unwrap();
+}
@@ -0,0 +1,19 @@
+#![feature(proc_macro)]
+
+extern crate maud;
+
+use maud::html;
+
+fn main() {
+    html!(@foo);  //~ ERROR unknown keyword `@foo`
+    html!(@ "foo");  //~ ERROR expected keyword after `@`
+    html!(p @);  //~ ERROR expected keyword after `@`
+    html!(p @let x = 1;);  //~ ERROR @let only works inside a block
+    html!(@collapse p);  //~ ERROR expected body for @collapse
+    html!(@collapse);  //~ ERROR expected body for @collapse
+    html!(a title=@flush {});  //~ ERROR @flush can't be used inside an attribute
+    html!(a title=@tag "b" {});  //~ ERROR unexpected element
+    html!(@tag "1x" {});  //~ ERROR invalid element name `1x`
+    html!(@tag p {});  //~ ERROR expected string literal or parenthesized element name after @tag
+    html!(@tag);  //~ ERROR expected string literal or parenthesized element name after @tag
+}
@@ -0,0 +1,19 @@
+#![feature(proc_macro)]
+
+extern crate maud;
+
+use maud::html;
+
+fn main() {
+    // Each error is reported, and the parser carries on after it
+    html! {
+        p { @foo }  //~ ERROR unknown keyword `@foo`
+        p #a #b { "Pinkie" }  //~ ERROR an element can only have one id
+        @if true {
+            @bar;  //~ ERROR unknown keyword `@bar`
+            "Rarity"
+        }
+        42  //~ ERROR expected string
+        "Applejack"
+    };
+}
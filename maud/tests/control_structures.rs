@@ -40,6 +40,52 @@ fn if_let() {
     }
 }
 
+#[test]
+fn if_let_struct_pattern() {
+    struct Pony { name: &'static str, is_best: bool }
+    for &(is_best, output) in &[(true, "Pinkie Pie is best pony"), (false, "oh noes")] {
+        let pony = Pony { name: "Pinkie Pie", is_best };
+        let s = html! {
+            @if let Pony { name, is_best: true } = pony {
+                (name) " is best pony"
+            } @else {
+                "oh noes"
+            }
+        }.into_string();
+        assert_eq!(s, output);
+    }
+}
+
+#[test]
+fn else_if_let() {
+    for &(input, output) in &[(Ok(1), "one"), (Err("two"), "two"), (Ok(3), "oh noes")] {
+        let s = html! {
+            @if let Ok(1) = input {
+                "one"
+            } @else if let Err(value) = input {
+                (value)
+            } @else {
+                "oh noes"
+            }
+        }.into_string();
+        assert_eq!(s, output);
+    }
+}
+
+#[test]
+fn if_let_range_pattern() {
+    for &(input, output) in &[(Some(3), "small"), (Some(30), "big")] {
+        let s = html! {
+            @if let Some(0...9) = input {
+                "small"
+            } @else {
+                "big"
+            }
+        }.into_string();
+        assert_eq!(s, output);
+    }
+}
+
 #[test]
 fn while_expr() {
     let mut numbers = (0..3).into_iter().peekable();
@@ -63,6 +109,34 @@ fn while_let_expr() {
     assert_eq!(s, "<ul><li>0</li><li>1</li><li>2</li></ul>");
 }
 
+#[test]
+fn while_let_struct_pattern() {
+    struct Wrapper { n: u32 }
+    let mut numbers = (0..3).map(|n| Wrapper { n });
+    #[cfg_attr(feature = "cargo-clippy", allow(while_let_on_iterator))]
+    let s = html! {
+        ul @while let Some(Wrapper { n }) = numbers.next() {
+            li (n)
+        }
+    }.into_string();
+    assert_eq!(s, "<ul><li>0</li><li>1</li><li>2</li></ul>");
+}
+
+#[test]
+fn while_break() {
+    let mut numbers = 0..;
+    #[cfg_attr(feature = "cargo-clippy", allow(while_let_on_iterator))]
+    let s = html! {
+        @while let Some(n) = numbers.next() {
+            @if n == 3 {
+                @break;
+            }
+            (n)
+        }
+    }.into_string();
+    assert_eq!(s, "012");
+}
+
 #[test]
 fn for_expr() {
     let ponies = ["Apple Bloom", "Scootaloo", "Sweetie Belle"];
@@ -79,6 +153,87 @@ fn for_expr() {
             "</ul>"));
 }
 
+#[test]
+fn for_empty() {
+    fn test(ponies: &[&str]) -> String {
+        html! {
+            ul @for pony in ponies {
+                li (pony)
+            } @empty {
+                li.empty "No ponies :("
+            }
+        }.into_string()
+    }
+    assert_eq!(test(&["Apple Bloom", "Scootaloo"]), concat!(
+            "<ul>",
+            "<li>Apple Bloom</li>",
+            "<li>Scootaloo</li>",
+            "</ul>"));
+    assert_eq!(test(&[]), r#"<ul><li class="empty">No ponies :(</li></ul>"#);
+}
+
+#[test]
+fn for_loop_info() {
+    let ponies = ["Apple Bloom", "Scootaloo", "Sweetie Belle"];
+    let s = html! {
+        @for (l, pony) in maud::loop_info(&ponies) {
+            span.first[l.first].odd[l.index % 2 == 1] (pony)
+            @if !l.last { ", " }
+        }
+    }.into_string();
+    assert_eq!(s, concat!(
+            r#"<span class="first">Apple Bloom</span>, "#,
+            r#"<span class="odd">Scootaloo</span>, "#,
+            r#"<span>Sweetie Belle</span>"#));
+}
+
+#[test]
+fn for_continue() {
+    let s = html! {
+        @for i in 0..6 {
+            @if i % 2 == 0 {
+                @continue
+            }
+            (i)
+        }
+    }.into_string();
+    assert_eq!(s, "135");
+}
+
+#[test]
+fn labeled_break() {
+    let rows = [[1, 2, 3], [4, 0, 6], [7, 8, 9]];
+    let s = html! {
+        @'rows: for row in &rows {
+            @for &cell in row {
+                @if cell == 0 {
+                    @break 'rows;
+                }
+                (cell)
+            }
+        }
+    }.into_string();
+    assert_eq!(s, "1234");
+}
+
+#[test]
+fn labeled_continue() {
+    let rows = [[1, 0, 3], [4, 5, 6]];
+    let s = html! {
+        @'rows: for row in &rows {
+            "["
+            @for &cell in row {
+                @if cell == 0 {
+                    @continue 'rows;
+                }
+                (cell)
+            }
+            "]"
+        }
+    }.into_string();
+    assert_eq!(s, "[1[456]");
+}
+
 #[test]
 fn match_expr() {
     for &(input, output) in &[(Some("yay"), "<div>yay</div>"), (None, "oh noes")] {
@@ -123,6 +278,21 @@ fn match_expr_with_guards() {
     }
 }
 
+#[test]
+fn match_expr_with_struct_patterns() {
+    struct Point { x: i32, y: i32 }
+    for &(x, y, output) in &[(0, 1, "on the y axis at 1"), (1, 2, "at 1, 2")] {
+        let s = html! {
+            @match (Point { x, y }) {
+                Point { x: 0, y } => { "on the y axis at " (y) },
+                Point { x, y } if { x > 0 } => { "at " (x) ", " (y) },
+                Point { .. } => "elsewhere",
+            }
+        }.into_string();
+        assert_eq!(s, output);
+    }
+}
+
 #[test]
 fn match_in_attribute() {
     for &(input, output) in &[(1, "<span class=\"one\">1</span>"), (2, "<span class=\"two\">2</span>"), (3, "<span class=\"many\">3</span>")] {
@@ -85,3 +85,310 @@ fn issue_97() {
 
     assert_eq!(html!((Pinkie)).into_string(), "42");
 }
+
+#[test]
+fn html_to_string() {
+    use maud::html_to;
+
+    let mut s = String::from("<!-- header -->");
+    let name = "<Pinkie Pie>";
+    html_to!(s, p { "Hello, " (name) "!" }).unwrap();
+    assert_eq!(s, "<!-- header --><p>Hello, &lt;Pinkie Pie&gt;!</p>");
+}
+
+#[test]
+fn html_to_parenthesized_writer() {
+    use maud::html_to;
+
+    fn writer<A, B>(s: &mut String) -> &mut String { s }
+    let mut s = String::new();
+    html_to!((*writer::<u8, u16>(&mut s)), p { "Hi" }).unwrap();
+    assert_eq!(s, "<p>Hi</p>");
+}
+
+#[test]
+fn html_to_io() {
+    use maud::{IoWriter, html_to};
+
+    let mut writer = IoWriter::new(Vec::new());
+    let result = html_to!(writer, ul { @for i in 1..4 { li (i) } });
+    let bytes = writer.finish(result).unwrap();
+    assert_eq!(bytes, b"<ul><li>1</li><li>2</li><li>3</li></ul>");
+}
+
+#[test]
+fn html_to_io_error() {
+    use maud::{IoWriter, html_to};
+    use std::io;
+
+    struct Broken;
+    impl io::Write for Broken {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "oh no"))
+        }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+
+    let mut writer = IoWriter::new(Broken);
+    let result = html_to!(writer, p { "Hello" });
+    assert!(result.is_err());
+    let error = writer.finish(result).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn render_impl_html_to() {
+    use maud::{Render, html_to};
+    use std::fmt;
+
+    struct Pinkie;
+    impl Render for Pinkie {
+        fn render_to(&self, buffer: &mut String) {
+            let _ = html_to!(*buffer, b { "Pinkie" });
+        }
+
+        fn render_fmt(&self, w: &mut fmt::Write) -> fmt::Result {
+            html_to!(*w, b { "Pinkie" })
+        }
+    }
+
+    assert_eq!(html!((Pinkie)).into_string(), "<b>Pinkie</b>");
+    let mut s = String::new();
+    html_to!(s, p { (Pinkie) }).unwrap();
+    assert_eq!(s, "<p><b>Pinkie</b></p>");
+}
+
+#[test]
+fn html_stream_chunks() {
+    use maud::html_stream;
+
+    let title = "Pinkie's page";
+    let page = html_stream! {
+        head { title (title) }
+        @flush
+        body { @for i in 1..3 { p (i) @flush; } }
+    };
+    let chunks: Vec<String> = page.into_iter().collect();
+    assert_eq!(chunks, vec![
+        "<head><title>Pinkie's page</title></head>".to_string(),
+        "<body><p>1</p>".to_string(),
+        "<p>2</p>".to_string(),
+        "</body>".to_string(),
+    ]);
+}
+
+#[test]
+fn html_stream_lazy() {
+    use maud::html_stream;
+    use std::sync::mpsc;
+
+    let (sender, receiver) = mpsc::channel();
+    let page = html_stream! {
+        head { title "Slow page" }
+        @flush
+        body { (receiver.recv().unwrap()) }
+    };
+    let mut chunks = page.into_iter();
+    // The body isn't computed until after the head has been sent
+    assert_eq!(chunks.next().unwrap(), "<head><title>Slow page</title></head>");
+    sender.send("Pinkie").unwrap();
+    assert_eq!(chunks.next().unwrap(), "<body>Pinkie</body>");
+    assert_eq!(chunks.next(), None);
+}
+
+#[test]
+#[should_panic]
+fn html_stream_panic() {
+    use maud::html_stream;
+
+    fn oops() -> &'static str {
+        panic!("Oops")
+    }
+
+    let page = html_stream!(p "Hello" @flush (oops()));
+    for _ in page {}
+}
+
+#[test]
+fn html_stream_write_to() {
+    use maud::html_stream;
+
+    let mut bytes = Vec::new();
+    html_stream!(p { "Hello" } @flush p { "world" }).write_to(&mut bytes).unwrap();
+    assert_eq!(bytes, b"<p>Hello</p><p>world</p>");
+}
+
+#[test]
+fn flush_ignored_in_html() {
+    let s = html!(p { "Hello" } @flush p { "world" }).into_string();
+    assert_eq!(s, "<p>Hello</p><p>world</p>");
+}
+
+#[test]
+fn pretty() {
+    let s = html! {
+        (maud::DOCTYPE)
+        html {
+            head { title "Pinkie's page" meta charset="utf-8"; }
+            body {
+                h1 { "Hello, " em "world" "!" }
+                ul { li "Pinkie" li { a href="/rarity" "Rarity" } }
+                pre { "  keep\n  this" }
+                p { span "inline" " " span "text" }
+            }
+        }
+    }.pretty().into_string();
+    assert_eq!(s, r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>Pinkie's page</title>
+    <meta charset="utf-8">
+  </head>
+  <body>
+    <h1>Hello, <em>world</em>!</h1>
+    <ul>
+      <li>Pinkie</li>
+      <li><a href="/rarity">Rarity</a></li>
+    </ul>
+    <pre>  keep
+  this</pre>
+    <p><span>inline</span> <span>text</span></p>
+  </body>
+</html>"#);
+}
+
+#[test]
+fn pretty_inline_only() {
+    let markup = html!(span "Pinkie" " " b "Pie");
+    assert_eq!(markup.pretty().into_string(), markup.into_string());
+}
+
+#[test]
+fn pretty_unbalanced() {
+    let markup = maud::PreEscaped("<div><p>Unclosed</div>");
+    assert_eq!(markup.pretty().into_string(), "<div><p>Unclosed</div>");
+}
+
+#[test]
+fn pretty_non_ascii() {
+    let markup = maud::PreEscaped("<div><p>é</p><p>ünïcode &lt;3</p></div>");
+    assert_eq!(markup.pretty().into_string(), "<div>\n  <p>é</p>\n  <p>ünïcode &lt;3</p>\n</div>");
+}
+
+#[test]
+fn pretty_textarea_inline() {
+    let markup = maud::PreEscaped("<form><textarea>a</textarea><textarea>b</textarea></form>");
+    assert_eq!(markup.pretty().into_string(), "<form><textarea>a</textarea><textarea>b</textarea></form>");
+}
+
+#[test]
+fn html_static() {
+    use maud::{PreEscaped, html_static};
+
+    const FOOTER: PreEscaped<&'static str> = html_static! {
+        footer { "Made with " a href="https://maud.lambda.xyz/" "Maud" }
+    };
+    static ICON: PreEscaped<&'static str> = html_static!(svg { use href="#pinkie"; });
+    assert_eq!(FOOTER.into_string(), r#"<footer>Made with <a href="https://maud.lambda.xyz/">Maud</a></footer>"#);
+    assert_eq!(ICON.0, r##"<svg xmlns="http://www.w3.org/2000/svg"><use href="#pinkie"/></svg>"##);
+    assert_eq!(html!((FOOTER) (ICON)).into_string(), format!("{}{}", FOOTER.0, ICON.0));
+}
+
+#[test]
+fn static_template() {
+    let s = html!(p { "Hello, " b "world" "!" }).into_string();
+    assert_eq!(s, "<p>Hello, <b>world</b>!</p>");
+}
+
+#[test]
+fn size_hint_for_loop() {
+    let s = html! {
+        ul {
+            @for i in 0..100 {
+                li { "Item " (i) }
+            }
+        }
+    }.into_string();
+    // Reserving up front should leave enough room for every item
+    assert!(s.capacity() >= 100 * "<li>Item </li>".len());
+    assert!(s.starts_with("<ul><li>Item 0</li><li>Item 1</li>"));
+}
+
+#[test]
+fn size_hint_for_unbounded_loop() {
+    let s = html! {
+        @for i in 0.. {
+            @if i == 3 { @break }
+            (i)
+        }
+        @for i in 0..1_000_000_000 {
+            @if i == 3 { @break }
+            (i)
+        }
+    }.into_string();
+    assert_eq!(s, "012012");
+    assert!(s.capacity() <= 1024 * 1024);
+}
+
+#[test]
+fn markup_value_type() {
+    use maud::Markup;
+
+    let mut markup = html!(b "Pinkie");
+    assert_eq!(markup.as_str(), "<b>Pinkie</b>");
+    assert_eq!(markup.len(), 13);
+    assert!(!markup.is_empty());
+    assert!(Markup::default().is_empty());
+    assert_eq!(format!("{}", markup), "<b>Pinkie</b>");
+
+    markup += html!(" & ");
+    markup = markup + &html!(i "Rarity");
+    assert_eq!(markup, html!(b "Pinkie" " & " i "Rarity"));
+}
+
+#[test]
+fn markup_from_iterator() {
+    use maud::{Markup, PreEscaped};
+
+    let ponies = ["Pinkie Pie", "Rarity"];
+    let mut markup: Markup = ponies.iter().map(|pony| html!(li (pony))).collect();
+    markup.extend(vec![PreEscaped("<li>Applejack</li>")]);
+    assert_eq!(markup.into_string(), "<li>Pinkie Pie</li><li>Rarity</li><li>Applejack</li>");
+
+    let markup = Markup::join(ponies.iter().map(|pony| html!(b (pony))), html!(br;));
+    assert_eq!(markup.into_string(), "<b>Pinkie Pie</b><br><b>Rarity</b>");
+}
+
+#[test]
+fn from_fn() {
+    let name = "<Pinkie>";
+    let fragment = maud::from_fn(|buffer| {
+        buffer.push_str("<hr>");
+        buffer.push_str(&name.len().to_string());
+    });
+    let s = html!(p (fragment) (fragment)).into_string();
+    assert_eq!(s, "<p><hr>8<hr>8</p>");
+}
+
+#[test]
+fn html_fn() {
+    use maud::{Markup, Render, html_fn};
+
+    fn layout<B: Render>(title: &str, body: B) -> Markup {
+        html! {
+            head { title (title) }
+            body { (body) }
+        }
+    }
+
+    let names = ["Pinkie", "<Rarity>"];
+    let greeting = html_fn! {
+        @for name in &names {
+            p { "Hello, " (name) "!" }
+        }
+    };
+    let s = layout("Hi", greeting).into_string();
+    assert_eq!(s, concat!(
+        "<head><title>Hi</title></head>",
+        "<body><p>Hello, Pinkie!</p><p>Hello, &lt;Rarity&gt;!</p></body>"));
+}
@@ -0,0 +1,36 @@
+#![cfg(feature = "serde")]
+
+#![feature(plugin)]
+#![feature(proc_macro)]
+
+#![plugin(maud_lints)]
+
+extern crate maud;
+extern crate serde_json;
+
+use maud::{Escaped, Markup, PreEscaped, html};
+
+#[test]
+fn pre_escaped() {
+    let markup = html!(p { "Pinkie & Rarity" });
+    let json = serde_json::to_string(&markup).unwrap();
+    assert_eq!(json, r#""<p>Pinkie &amp; Rarity</p>""#);
+    assert_eq!(serde_json::to_string(&PreEscaped("<br>")).unwrap(), r#""<br>""#);
+}
+
+#[test]
+fn trusted() {
+    let json = r#""<p>Pinkie &amp; Rarity</p>""#;
+    let markup: Markup = maud::serde::trusted::deserialize(&mut serde_json::Deserializer::from_str(json)).unwrap();
+    assert_eq!(html!(div (markup)).into_string(), "<div><p>Pinkie &amp; Rarity</p></div>");
+    let mut buffer = Vec::new();
+    maud::serde::trusted::serialize(&markup, &mut serde_json::Serializer::new(&mut buffer)).unwrap();
+    assert_eq!(String::from_utf8(buffer).unwrap(), json);
+}
+
+#[test]
+fn escaped() {
+    let name: Escaped<String> = serde_json::from_str(r#""<Pinkie>""#).unwrap();
+    assert_eq!(html!(p (name)).into_string(), "<p>&lt;Pinkie&gt;</p>");
+    assert_eq!(serde_json::to_string(&name).unwrap(), r#""<Pinkie>""#);
+}
@@ -107,3 +107,178 @@ fn nested_macro_invocation() {
     let s = html!((format!("{} is best pony", best_pony))).into_string();
     assert_eq!(s, "Pinkie Pie is best pony");
 }
+
+#[test]
+fn unsafe_urls() {
+    for url in &["javascript:alert(1)", " JavaScript:alert(1)", "java\tscript:alert(1)", "data:text/html,hi"] {
+        let s = html!(a href=(url) "Click me!").into_string();
+        assert_eq!(s, r#"<a href="about:invalid#maud">Click me!</a>"#);
+    }
+}
+
+#[test]
+fn safe_urls() {
+    let s = html!(a href=("/pinkie?a=1&b=2") {} img src=("https://example.com/pie.png");).into_string();
+    assert_eq!(s, r#"<a href="/pinkie?a=1&amp;b=2"></a><img src="https://example.com/pie.png">"#);
+}
+
+#[test]
+fn url_scheme_in_later_splice() {
+    let q = "data:x";
+    let s = html!(a href={ "/search?q=" (q) } {}).into_string();
+    assert_eq!(s, r#"<a href="/search?q=data:x"></a>"#);
+}
+
+#[test]
+fn url_scheme_split_across_splices() {
+    let scheme = "javascript";
+    let s = html!(a href={ (scheme) ":alert(1)" } {}).into_string();
+    assert_eq!(s, r#"<a href="about:invalid#maud"></a>"#);
+}
+
+#[test]
+fn trusted_urls() {
+    use maud::TrustedUrl;
+    let s = html!(img src=(TrustedUrl("data:image/gif;base64,R0lG")););
+    assert_eq!(s.into_string(), r#"<img src="data:image/gif;base64,R0lG">"#);
+}
+
+#[test]
+fn unsafe_urls_optional_and_spread() {
+    let url = Some("javascript:alert(1)");
+    let attrs = vec![("href", "javascript:alert(1)"), ("title", "javascript:alert(1)")];
+    let s = html!(a href=[url] {} a ..(attrs) {}).into_string();
+    assert_eq!(s, concat!(
+        r#"<a href="about:invalid#maud"></a>"#,
+        r#"<a href="about:invalid#maud" title="javascript:alert(1)"></a>"#));
+}
+
+#[test]
+fn javascript_escaping() {
+    let name = "</script><script>alert('Pinkie')";
+    let s = html! {
+        script { "var name = '" (name) "';" }
+        button onclick={ "greet('" (name) "')" } "Hi"
+    }.into_string();
+    assert_eq!(s, concat!(
+        r#"<script>var name = '\u003c\u002fscript\u003e\u003cscript\u003ealert(\u0027Pinkie\u0027)';</script>"#,
+        r#"<button onclick="greet('\u003c\u002fscript\u003e\u003cscript\u003ealert(\u0027Pinkie\u0027)')">Hi</button>"#));
+}
+
+#[test]
+fn event_handler_names() {
+    let value = "it's";
+    let s = html!(div onclick=(value) on-tap=(value) {}).into_string();
+    assert_eq!(s, r#"<div onclick="it\u0027s" on-tap="it's"></div>"#);
+}
+
+#[test]
+fn css_escaping() {
+    let color = "red; background: url(evil)";
+    let s = html!(p style={ "color: " (color) } { style { "p { color: " (color) " }" } }).into_string();
+    assert_eq!(s, concat!(
+        r#"<p style="color: red\3b  background\3a  url\28 evil\29 ">"#,
+        r#"<style>p { color: red\3b  background\3a  url\28 evil\29  }</style></p>"#));
+}
+
+#[test]
+fn pre_escaped_in_script() {
+    use maud::PreEscaped;
+    let code = PreEscaped("alert(\"Pinkie\")");
+    let s = html!(script { (code) }).into_string();
+    assert_eq!(s, r#"<script>alert("Pinkie")</script>"#);
+}
+
+#[test]
+fn display_impl() {
+    use std::fmt;
+    struct Pony(&'static str);
+    impl fmt::Display for Pony {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "<{}>", self.0)
+        }
+    }
+
+    let pinkie = Pony("Pinkie Pie");
+    let s = html!((pinkie) " " (&pinkie)).into_string();
+    assert_eq!(s, "&lt;Pinkie Pie&gt; &lt;Pinkie Pie&gt;");
+}
+
+#[test]
+fn references() {
+    let markup = html!(b "Pinkie");
+    let names = vec![String::from("<Rarity>")];
+    let s = html!((&markup) @for name in &names { (name) (&name) }).into_string();
+    assert_eq!(s, "<b>Pinkie</b>&lt;Rarity&gt;&lt;Rarity&gt;");
+}
+
+#[test]
+fn escaped() {
+    use maud::{Escaped, Markup, Render};
+    use std::net::Ipv4Addr;
+
+    fn list_item<T: Render>(item: T) -> Markup {
+        html!(li (item))
+    }
+
+    let s = list_item(Escaped(Ipv4Addr::new(127, 0, 0, 1))).into_string();
+    assert_eq!(s, "<li>127.0.0.1</li>");
+}
+
+#[test]
+fn options() {
+    let pinkie = Some("Pinkie Pie");
+    let rarity: Option<&str> = None;
+    let s = html!((pinkie) "|" (rarity)).into_string();
+    assert_eq!(s, "Pinkie Pie|");
+}
+
+#[test]
+fn smart_pointers() {
+    use std::borrow::Cow;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    let boxed: Box<str> = "<Pinkie>".into();
+    let cow: Cow<str> = Cow::Borrowed("<Rarity>");
+    let markup = Rc::new(html!(b "Applejack"));
+    let s = html!((boxed) (cow) (markup) (Arc::new(20))).into_string();
+    assert_eq!(s, "&lt;Pinkie&gt;&lt;Rarity&gt;<b>Applejack</b>20");
+}
+
+#[test]
+fn slices() {
+    let ponies = vec!["<Pinkie>", "Rarity"];
+    let s = html!((ponies) (&ponies[1..])).into_string();
+    assert_eq!(s, "&lt;Pinkie&gt;RarityRarity");
+}
+
+#[test]
+fn join() {
+    use maud::Join;
+    let ponies = ["Pinkie Pie", "Rarity", "Applejack"];
+    let items = ponies.iter().map(|pony| html!(b (pony))).collect::<Vec<_>>();
+    let s = html!((Join(&ponies, ", ")) " " (Join(&items, html!(br;)))).into_string();
+    assert_eq!(s, concat!(
+        "Pinkie Pie, Rarity, Applejack ",
+        "<b>Pinkie Pie</b><br><b>Rarity</b><br><b>Applejack</b>"));
+}
+
+#[test]
+fn results() {
+    use maud::{Escaped, Render};
+
+    let ok: Result<&str, String> = Ok("Pinkie Pie");
+    let err: Result<&str, String> = Err(String::from("<no pony>"));
+    let s = html!((ok) "|" (err)).into_string();
+    assert_eq!(s, "Pinkie Pie|");
+
+    maud::set_error_hook(|error, buffer| {
+        buffer.push_str("<span class=\"error\">");
+        Escaped(error).render_to(buffer);
+        buffer.push_str("</span>");
+    });
+    let s = html!((err)).into_string();
+    maud::take_error_hook();
+    assert_eq!(s, r#"<span class="error">&lt;no pony&gt;</span>"#);
+}
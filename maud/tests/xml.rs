@@ -0,0 +1,77 @@
+#![feature(plugin)]
+#![feature(proc_macro)]
+
+#![plugin(maud_lints)]
+
+extern crate maud;
+
+use maud::xml;
+
+#[test]
+fn void_elements() {
+    let s = xml!(link href="https://example.com/"; br /).into_string();
+    assert_eq!(s, r#"<link href="https://example.com/"/><br/>"#);
+}
+
+#[test]
+fn empty_attributes() {
+    let s = xml!(input type="checkbox" checked? disabled?[false];).into_string();
+    assert_eq!(s, r#"<input type="checkbox" checked="checked"/>"#);
+}
+
+#[test]
+fn empty_attributes_spread() {
+    let attrs = vec![("checked", true), ("disabled", false)];
+    let s = xml!(input ..(attrs);).into_string();
+    assert_eq!(s, r#"<input checked="checked"/>"#);
+}
+
+#[test]
+fn no_html_escaping_contexts() {
+    let value = "it's <1>";
+    let s = xml!(device online=(value) { style (value) script (value) }).into_string();
+    assert_eq!(s, concat!(
+        r#"<device online="it&#39;s &lt;1&gt;">"#,
+        "<style>it&#39;s &lt;1&gt;</style><script>it&#39;s &lt;1&gt;</script></device>"));
+}
+
+#[test]
+fn single_quotes() {
+    let name = "Pinkie's party";
+    let s = xml!(title { "Pinkie's page" } summary (name)).into_string();
+    assert_eq!(s, "<title>Pinkie&#39;s page</title><summary>Pinkie&#39;s party</summary>");
+}
+
+#[test]
+fn single_quotes_in_html() {
+    use maud::html;
+
+    let name = "Pinkie's party";
+    let s = html!(p { "Pinkie's page" } p (name)).into_string();
+    assert_eq!(s, "<p>Pinkie's page</p><p>Pinkie's party</p>");
+}
+
+#[test]
+fn atom_feed() {
+    use maud::XML_DECLARATION;
+
+    let entries = vec!["First post", "Second post"];
+    let s = xml! {
+        (XML_DECLARATION)
+        feed xmlns="http://www.w3.org/2005/Atom" {
+            title "Pinkie's blog"
+            link href="https://example.com/";
+            @for entry in &entries {
+                entry { title (entry) }
+            }
+        }
+    }.into_string();
+    assert_eq!(s, concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        r#"<feed xmlns="http://www.w3.org/2005/Atom">"#,
+        "<title>Pinkie&#39;s blog</title>",
+        r#"<link href="https://example.com/"/>"#,
+        "<entry><title>First post</title></entry>",
+        "<entry><title>Second post</title></entry>",
+        "</feed>"));
+}
@@ -20,7 +20,8 @@ use std::fmt;
 /// All other characters are passed through unchanged.
 ///
 /// **Note:** In versions prior to 0.13, the single quote (`'`) was
-/// escaped as well.
+/// escaped as well. An `Escaper` created with `Escaper::xml()` still
+/// escapes it, as `&#39;`.
 ///
 /// # Example
 ///
@@ -30,34 +31,83 @@ use std::fmt;
 /// write!(Escaper::new(&mut s), "<script>launchMissiles()</script>").unwrap();
 /// assert_eq!(s, "&lt;script&gt;launchMissiles()&lt;/script&gt;");
 /// ```
-pub struct Escaper<'a>(&'a mut String);
+pub struct Escaper<'a, W: 'a + ?Sized = String> {
+    inner: &'a mut W,
+    xml: bool,
+}
+
+impl<'a, W: ?Sized> Escaper<'a, W> {
+    /// Creates an `Escaper` from a `String`, or any other `fmt::Write`.
+    pub fn new(buffer: &'a mut W) -> Escaper<'a, W> {
+        Escaper { inner: buffer, xml: false }
+    }
 
-impl<'a> Escaper<'a> {
-    /// Creates an `Escaper` from a `String`.
-    pub fn new(buffer: &'a mut String) -> Escaper<'a> {
-        Escaper(buffer)
+    /// Creates an `Escaper` which also escapes single quotes, for use in
+    /// XML documents.
+    pub fn xml(buffer: &'a mut W) -> Escaper<'a, W> {
+        Escaper { inner: buffer, xml: true }
     }
 }
 
-impl<'a> fmt::Write for Escaper<'a> {
+impl<'a, W: fmt::Write + ?Sized> fmt::Write for Escaper<'a, W> {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for b in s.bytes() {
-            match b {
-                b'&' => self.0.push_str("&amp;"),
-                b'<' => self.0.push_str("&lt;"),
-                b'>' => self.0.push_str("&gt;"),
-                b'"' => self.0.push_str("&quot;"),
-                _ => unsafe { self.0.as_mut_vec().push(b) },
-            }
+        // Write out unescaped runs of text in one go
+        let mut start = 0;
+        for (i, b) in s.bytes().enumerate() {
+            let escaped = match b {
+                b'&' => "&amp;",
+                b'<' => "&lt;",
+                b'>' => "&gt;",
+                b'"' => "&quot;",
+                b'\'' if self.xml => "&#39;",
+                _ => continue,
+            };
+            self.inner.write_str(&s[start..i])?;
+            self.inner.write_str(escaped)?;
+            start = i + 1;
         }
-        Ok(())
+        self.inner.write_str(&s[start..])
     }
 }
 
+/// Returns whether the given string is a valid HTML attribute name.
+///
+/// The [HTML spec] allows any character in an attribute name, except for
+/// controls, noncharacters, spaces, and the characters `"`, `'`, `>`, `/`
+/// and `=`.
+///
+/// [HTML spec]: https://html.spec.whatwg.org/multipage/syntax.html#attributes-2
+pub fn is_valid_attribute_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| match c {
+        ' ' | '"' | '\'' | '>' | '/' | '=' => false,
+        '\u{FDD0}' ... '\u{FDEF}' => false,
+        c => !c.is_control() && (c as u32) & 0xFFFE != 0xFFFE,
+    })
+}
+
+/// Returns whether the given string is safe to use as an HTML element name.
+///
+/// A valid name starts with an ASCII letter, followed by ASCII letters,
+/// digits, `-`, `.`, `_`, `:` or non-ASCII characters. This covers both
+/// standard and [custom elements].
+///
+/// [custom elements]: https://html.spec.whatwg.org/multipage/custom-elements.html#valid-custom-element-name
+pub fn is_valid_element_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some('a' ... 'z') | Some('A' ... 'Z') => {},
+        _ => return false,
+    }
+    chars.all(|c| match c {
+        'a' ... 'z' | 'A' ... 'Z' | '0' ... '9' | '-' | '.' | '_' | ':' => true,
+        c => c as u32 >= 0x80,
+    })
+}
+
 #[cfg(test)]
 mod test {
     use std::fmt::Write;
-    use Escaper;
+    use {Escaper, is_valid_attribute_name, is_valid_element_name};
 
     #[test]
     fn it_works() {
@@ -65,4 +115,48 @@ mod test {
         write!(Escaper::new(&mut s), "<script>launchMissiles()</script>").unwrap();
         assert_eq!(s, "&lt;script&gt;launchMissiles()&lt;/script&gt;");
     }
+
+    #[test]
+    fn single_quotes() {
+        let mut s = String::new();
+        write!(Escaper::new(&mut s), "Pinkie's party").unwrap();
+        assert_eq!(s, "Pinkie's party");
+        let mut s = String::new();
+        write!(Escaper::xml(&mut s), "Pinkie's party").unwrap();
+        assert_eq!(s, "Pinkie&#39;s party");
+    }
+
+    #[test]
+    fn any_writer() {
+        struct Counter(usize);
+        impl Write for Counter {
+            fn write_str(&mut self, s: &str) -> ::std::fmt::Result {
+                self.0 += s.len();
+                Ok(())
+            }
+        }
+        let mut counter = Counter(0);
+        write!(Escaper::new(&mut counter), "<b>").unwrap();
+        assert_eq!(counter.0, "&lt;b&gt;".len());
+    }
+
+    #[test]
+    fn attribute_names() {
+        for name in &["class", "data-2col", "@click", ":class", "x-on:click.prevent"] {
+            assert!(is_valid_attribute_name(name), "{}", name);
+        }
+        for name in &["", "a b", "a\"b", "a'b", "a>b", "a/b", "a=b", "a\nb"] {
+            assert!(!is_valid_attribute_name(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn element_names() {
+        for name in &["h1", "my-el-2", "svg:rect", "math-α"] {
+            assert!(is_valid_element_name(name), "{}", name);
+        }
+        for name in &["", "2col", "-x", "a b", "a>b", "a/b", "a\"b"] {
+            assert!(!is_valid_element_name(name), "{:?}", name);
+        }
+    }
 }
@@ -1,21 +1,86 @@
-use proc_macro::{Delimiter, Literal, Span, TokenNode, TokenStream, TokenTree};
+use proc_macro::{Delimiter, Literal, Span, Term, TokenNode, TokenStream, TokenTree};
 use proc_macro::quote;
 
 use maud_htmlescape::Escaper;
 
+/// A guess at the length of each splice, in bytes.
+const SPLICE_SIZE_HINT: usize = 16;
+
+/// The kind of document being generated.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Html,
+    /// Well-formed XML, as generated by `xml!`.
+    Xml,
+}
+
+/// Where a splice appears, which determines how it's escaped.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    /// Text, or the value of an ordinary attribute.
+    Text,
+    /// A URL attribute, such as `href`.
+    Url,
+    /// An event handler attribute, or the body of a `script` element.
+    Js,
+    /// A `style` attribute or element.
+    Css,
+}
+
+impl Context {
+    /// Returns the context for the value of the given attribute.
+    ///
+    /// In XML, only URL attributes are treated specially, since the other
+    /// attributes and elements here may mean something else entirely in an
+    /// arbitrary XML vocabulary.
+    pub fn attribute(name: &str, mode: Mode) -> Context {
+        match (&*name.to_ascii_lowercase(), mode) {
+            ("href", _) | ("src", _) | ("action", _) | ("formaction", _) | ("xlink:href", _) => Context::Url,
+            (_, Mode::Xml) => Context::Text,
+            ("style", Mode::Html) => Context::Css,
+            (name, Mode::Html) if is_event_handler(name) => Context::Js,
+            _ => Context::Text,
+        }
+    }
+
+    /// Returns the context for the body of the given element.
+    pub fn element(name: &str, mode: Mode) -> Context {
+        match (&*name.to_ascii_lowercase(), mode) {
+            (_, Mode::Xml) => Context::Text,
+            ("script", Mode::Html) => Context::Js,
+            ("style", Mode::Html) => Context::Css,
+            _ => Context::Text,
+        }
+    }
+}
+
+/// Returns whether the given lower case attribute name is that of an event
+/// handler, as in `onclick`.
+fn is_event_handler(name: &str) -> bool {
+    name.len() > 2 && name.starts_with("on") && name[2..].bytes().all(|b| b'a' <= b && b <= b'z')
+}
+
 pub struct Builder {
     output_ident: TokenTree,
+    mode: Mode,
     stmts: Vec<TokenStream>,
     tail: String,
+    /// Whether to trim whitespace from the start of the next string.
+    trim_next: bool,
+    /// An estimate of the length of the output, in bytes.
+    size_hint: usize,
 }
 
 impl Builder {
     /// Creates a new `Builder`.
-    pub fn new(output_ident: TokenTree) -> Builder {
+    pub fn new(output_ident: TokenTree, mode: Mode) -> Builder {
         Builder {
             output_ident,
+            mode,
             stmts: Vec::new(),
             tail: String::new(),
+            trim_next: false,
+            size_hint: 0,
         }
     }
 
@@ -38,37 +103,141 @@ impl Builder {
         stmts.into_iter().collect()
     }
 
+    /// If the template is completely static, returns its contents as a
+    /// string. Otherwise returns the `Builder` unchanged.
+    pub fn into_static(self) -> Result<String, Builder> {
+        if self.stmts.is_empty() {
+            Ok(self.tail)
+        } else {
+            Err(self)
+        }
+    }
+
     /// Pushes a statement, flushing the tail buffer in the process.
     pub fn push<T>(&mut self, stmt: T) where T: Into<TokenStream> {
         self.flush();
+        self.trim_next = false;
         self.stmts.push(stmt.into())
     }
 
+    /// Returns an estimate of the length of the output, in bytes.
+    ///
+    /// This counts literal text exactly, and adds a guess for each splice.
+    /// Loops reserve their own space at runtime, so they aren't counted here.
+    pub fn size_hint(&self) -> usize {
+        self.size_hint
+    }
+
     /// Pushes a literal string to the tail buffer.
     fn push_str(&mut self, s: &str) {
+        self.trim_next = false;
+        self.size_hint += s.len();
         self.tail.push_str(s);
     }
 
     /// Appends a literal string.
     pub fn string(&mut self, s: &str) {
-        self.push_str(&html_escape(s));
+        let s = if self.trim_next { s.trim_left_matches(is_whitespace) } else { s };
+        if !s.is_empty() {
+            let escaped = escape(s, self.mode);
+            self.push_str(&escaped);
+        }
+    }
+
+    /// Appends a literal string, with each run of whitespace collapsed into a
+    /// single space.
+    pub fn string_collapsed(&mut self, s: &str) {
+        let mut collapsed = String::with_capacity(s.len());
+        let mut in_whitespace = self.tail.ends_with(is_whitespace);
+        for c in s.chars() {
+            if is_whitespace(c) {
+                if !in_whitespace {
+                    collapsed.push(' ');
+                }
+                in_whitespace = true;
+            } else {
+                collapsed.push(c);
+                in_whitespace = false;
+            }
+        }
+        self.string(&collapsed);
+    }
+
+    /// Removes whitespace from the end of the literal text so far, and from
+    /// the start of the next literal string.
+    pub fn trim(&mut self) {
+        let len = self.tail.trim_right_matches(is_whitespace).len();
+        self.size_hint -= self.tail.len() - len;
+        self.tail.truncate(len);
+        self.trim_next = true;
     }
 
     /// Appends the result of an expression.
     pub fn splice(&mut self, expr: TokenStream) {
+        self.splice_in(expr, Context::Text);
+    }
+
+    /// Appends the result of an expression, escaped for the given context.
+    pub fn splice_in(&mut self, expr: TokenStream, context: Context) {
+        self.size_hint += SPLICE_SIZE_HINT;
         let output_ident = self.output_ident.clone();
+        let method = match (context, self.mode) {
+            (Context::Text, Mode::Html) => "__maud_render_to",
+            (Context::Text, Mode::Xml) => "__maud_render_xml",
+            (Context::Url, _) => "__maud_render_url",
+            (Context::Js, _) => "__maud_render_js",
+            (Context::Css, _) => "__maud_render_css",
+        };
+        let method = TokenTree { kind: TokenNode::Term(Term::intern(method)), span: Span::def_site() };
         self.push(quote!({
-            // Create a local trait alias so that autoref works
-            trait Render: maud::Render {
-                fn __maud_render_to(&self, output_ident: &mut String) {
-                    maud::Render::render_to(self, output_ident);
-                }
-            }
-            impl<T: maud::Render> Render for T {}
-            $expr.__maud_render_to(&mut $output_ident);
+            // Values which implement `Render` are rendered through the first
+            // trait, and any other values which implement `Display` through
+            // the second. Types like `PreEscaped` have an inherent method with
+            // the same name on `Splice<PreEscaped<_>>`, which takes precedence
+            // over both (see `maud::macro_private::Splice`)
+            use maud::macro_private::{DisplaySplice, RenderSplice};
+            (&maud::macro_private::Splice(&($expr))).$method(&mut $output_ident);
         }));
     }
 
+    /// Appends an attribute value built by `value`, which is rendered to the
+    /// variable given by `url_ident()`. If the value isn't static, then it's
+    /// replaced at runtime if it's a URL which could run a script.
+    pub fn url_value(&mut self, value: Builder) {
+        match value.into_static() {
+            // Text written in the template is trusted
+            Ok(s) => self.push_str(&s),
+            Err(value) => {
+                self.size_hint += value.size_hint();
+                let url_ident = url_ident();
+                let output_ident = self.output_ident.clone();
+                let stmts = value.build();
+                self.push(quote!({
+                    let mut $url_ident = String::new();
+                    $stmts
+                    maud::macro_private::push_url(&$url_ident, &mut $output_ident);
+                }));
+            },
+        }
+    }
+
+    /// Wraps the iterator of a `@for` loop, so that it reserves enough space
+    /// for the whole loop before it starts.
+    pub fn reserve_for(&self, iter: TokenStream, body_size_hint: usize) -> TokenStream {
+        if body_size_hint == 0 {
+            return iter;
+        }
+        let output_ident = self.output_ident.clone();
+        let body_size_hint = TokenNode::Literal(Literal::u64(body_size_hint as u64));
+        quote!(maud::macro_private::reserve_for(($iter), &mut $output_ident, $body_size_hint as usize))
+    }
+
+    /// Marks a point where the output so far can be sent off as a chunk.
+    pub fn flush_point(&mut self) {
+        let output_ident = self.output_ident.clone();
+        self.push(quote!(maud::macro_private::Output::flush(&mut $output_ident);));
+    }
+
     pub fn element_open_start(&mut self, name: &str) {
         self.push_str("<");
         self.push_str(name);
@@ -81,24 +250,216 @@ impl Builder {
     }
 
     pub fn attribute_empty(&mut self, name: &str) {
-        self.push_str(" ");
-        self.push_str(name);
+        match self.mode {
+            Mode::Html => {
+                self.push_str(" ");
+                self.push_str(name);
+            },
+            // XML has no empty attributes, so repeat the name as the value
+            Mode::Xml => {
+                self.attribute_start(name);
+                self.push_str(name);
+                self.attribute_end();
+            },
+        }
     }
 
     pub fn attribute_end(&mut self) {
         self.push_str("\"");
     }
 
+    /// Starts a list of class names which are toggled at runtime.
+    ///
+    /// If `is_open` is set, then the class attribute has already been
+    /// started with at least one name. Otherwise it's started by the first
+    /// name which is toggled on, and must be ended with `class_list_end`.
+    pub fn class_list_start(&mut self, is_open: bool) {
+        let class_empty = class_empty_ident();
+        let is_empty = TokenTree {
+            kind: TokenNode::Term(Term::intern(if is_open { "false" } else { "true" })),
+            span: Span::def_site(),
+        };
+        self.push(quote!(let mut $class_empty = $is_empty;));
+    }
+
+    /// Writes what goes before a class name which was toggled on: the start
+    /// of the class attribute if it's the first name, or a space otherwise.
+    pub fn class_separator(&mut self) {
+        let output_ident = self.output_ident.clone();
+        let class_empty = class_empty_ident();
+        self.push(quote!(maud::macro_private::class_separator(&mut $output_ident, &mut $class_empty);));
+    }
+
+    /// Ends the class attribute started by `class_separator`, if any.
+    pub fn class_list_end(&mut self) {
+        let output_ident = self.output_ident.clone();
+        let class_empty = class_empty_ident();
+        self.push(quote!(if !$class_empty { $output_ident.push_str("\""); }));
+    }
+
+    /// Declares the variable which holds the `class` and `id` values of the
+    /// attribute spreads on an element. This must come before the first
+    /// `attribute_spread`.
+    pub fn spread_start(&mut self) {
+        let spread = spread_ident();
+        self.push(quote!(let mut $spread = maud::macro_private::SpreadAttributes::new();));
+    }
+
+    /// Emits a sequence of attributes, given an iterator of name-value
+    /// pairs. The `class` and `id` values are set aside, to be written by
+    /// `spread_class` and `spread_id`.
+    pub fn attribute_spread(&mut self, expr: TokenStream) {
+        let output_ident = self.output_ident.clone();
+        let spread = spread_ident();
+        let render_attributes = match self.mode {
+            Mode::Html => Term::intern("render_attributes"),
+            Mode::Xml => Term::intern("render_xml_attributes"),
+        };
+        let render_attributes = TokenTree {
+            kind: TokenNode::Term(render_attributes),
+            span: Span::def_site(),
+        };
+        self.push(quote!(maud::macro_private::$render_attributes(($expr), &mut $output_ident, &mut $spread);));
+    }
+
+    /// Appends the classes from the attribute spreads on an element to a
+    /// list started by `class_list_start`.
+    pub fn spread_class(&mut self) {
+        let output_ident = self.output_ident.clone();
+        let spread = spread_ident();
+        let class_empty = class_empty_ident();
+        self.push(quote!(maud::macro_private::push_spread_class(&$spread, &mut $output_ident, &mut $class_empty);));
+    }
+
+    /// Emits the ID from the attribute spreads on an element, if any.
+    pub fn spread_id(&mut self) {
+        let output_ident = self.output_ident.clone();
+        let spread = spread_ident();
+        self.push(quote!(maud::macro_private::push_spread_id(&$spread, &mut $output_ident);));
+    }
+
+    /// Emits an attribute whose value is an `Option`. If the value is
+    /// `None`, then the attribute is omitted entirely.
+    pub fn attribute_optional(&mut self, name: &str, value: TokenStream, value_span: Span) {
+        let (cond, inner_value) = if_let_some(value, value_span);
+        let body = {
+            let mut builder = Builder::new(self.output_ident.clone(), self.mode);
+            builder.attribute_start(name);
+            builder.splice_in(inner_value, Context::attribute(name, self.mode));
+            builder.attribute_end();
+            builder.build()
+        };
+        self.emit_if(cond, value_span, body);
+    }
+
     pub fn element_open_end(&mut self) {
         self.push_str(">");
     }
 
+    /// Ends the opening tag of a void element, which has no closing tag.
+    ///
+    /// In HTML, the tag is self-closing only if `self_closing` is set, as is
+    /// required for SVG and MathML elements.
+    pub fn element_open_end_void(&mut self, self_closing: bool) {
+        if self_closing || self.mode == Mode::Xml {
+            self.push_str("/>");
+        } else {
+            self.push_str(">");
+        }
+    }
+
     pub fn element_close(&mut self, name: &str) {
         self.push_str("</");
         self.push_str(name);
         self.push_str(">");
     }
 
+    /// Like `element_open_start`, but for the element name bound by
+    /// `emit_tag`.
+    pub fn element_open_start_spliced(&mut self) {
+        self.push_str("<");
+        self.tag_name();
+    }
+
+    /// Like `element_close`, but for the element name bound by `emit_tag`.
+    pub fn element_close_spliced(&mut self) {
+        self.push_str("</");
+        self.tag_name();
+        self.push_str(">");
+    }
+
+    /// Appends the element name bound by `emit_tag`.
+    fn tag_name(&mut self) {
+        let output_ident = self.output_ident.clone();
+        let tag_ident = tag_ident();
+        self.push(quote!($output_ident.push_str($tag_ident.as_str());));
+    }
+
+    /// Emits a block that checks the given element name, and binds it for
+    /// use by `element_open_start_spliced` and `element_close_spliced`.
+    pub fn emit_tag(&mut self, name: TokenStream, name_span: Span, body: TokenStream) {
+        let tag_ident = tag_ident();
+        let name = TokenTree {
+            kind: TokenNode::Group(Delimiter::Parenthesis, name),
+            span: name_span,
+        };
+        self.push(quote!({
+            let $tag_ident = maud::macro_private::tag_name($name);
+            $body
+        }));
+    }
+
+    /// Emits a `for` loop, followed by a block that runs only if the loop
+    /// body never did.
+    ///
+    /// The head is everything from the `for` keyword up to, but not
+    /// including, the loop body.
+    pub fn emit_for_empty(&mut self, head: TokenStream, body: TokenTree, empty: TokenTree) {
+        let flag = loop_empty_ident();
+        self.push(quote!({
+            let mut $flag = true;
+            $head {
+                $flag = false;
+                $body
+            }
+            if $flag $empty
+        }));
+    }
+
+    /// Emits a loop over the items of a `Stream`, as in
+    /// `@for await x in stream { ... }`, for use in `html_async!`.
+    ///
+    /// The label is either empty, or a loop label followed by a colon. An
+    /// error from the stream is returned from the enclosing async block.
+    pub fn emit_for_await(&mut self, label: TokenStream, pat: TokenStream, stream: TokenStream, body: TokenTree) {
+        let stream_ident = TokenTree {
+            kind: TokenNode::Term(Term::intern("__maud_stream")),
+            span: Span::def_site(),
+        };
+        // `await!` and the `Stream` trait are resolved where the caller
+        // imported them from `futures-await`
+        let await_ident = TokenTree {
+            kind: TokenNode::Term(Term::intern("await")),
+            span: Span::call_site(),
+        };
+        let into_future = TokenTree {
+            kind: TokenNode::Term(Term::intern("into_future")),
+            span: Span::call_site(),
+        };
+        self.push(quote!({
+            let mut $stream_ident = ($stream);
+            $label loop {
+                let (item, rest) = $await_ident!($stream_ident.$into_future()).map_err(|(error, _)| error)?;
+                $stream_ident = rest;
+                let $pat = match item {
+                    Some(item) => item,
+                    None => break,
+                };
+                $body
+            }
+        }));
+    }
+
     /// Emits an `if` expression.
     ///
     /// The condition is a token stream (not an expression) so we don't
@@ -124,9 +485,76 @@ impl Builder {
     }
 }
 
-fn html_escape(s: &str) -> String {
+/// Returns the variable used by `Builder::url_value`.
+pub fn url_ident() -> TokenTree {
+    TokenTree {
+        kind: TokenNode::Term(Term::intern("__maud_url")),
+        span: Span::def_site(),
+    }
+}
+
+/// Returns the variable used by `Builder::class_list_start`.
+fn class_empty_ident() -> TokenTree {
+    TokenTree {
+        kind: TokenNode::Term(Term::intern("__maud_class_empty")),
+        span: Span::def_site(),
+    }
+}
+
+/// Returns the variable declared by `Builder::spread_start`.
+fn spread_ident() -> TokenTree {
+    TokenTree {
+        kind: TokenNode::Term(Term::intern("__maud_spread")),
+        span: Span::def_site(),
+    }
+}
+
+/// Returns the variable used by `Builder::emit_for_empty`.
+fn loop_empty_ident() -> TokenTree {
+    TokenTree {
+        kind: TokenNode::Term(Term::intern("__maud_loop_empty")),
+        span: Span::def_site(),
+    }
+}
+
+/// Returns the variable bound by `Builder::emit_tag`.
+fn tag_ident() -> TokenTree {
+    TokenTree {
+        kind: TokenNode::Term(Term::intern("__maud_tag_name")),
+        span: Span::def_site(),
+    }
+}
+
+/// Returns a condition of the form `let Some(ref x) = (value)`, for use with
+/// `Builder::emit_if`, along with the variable `x` that it binds.
+pub fn if_let_some(value: TokenStream, value_span: Span) -> (TokenStream, TokenStream) {
+    let inner_value = TokenTree {
+        kind: TokenNode::Term(Term::intern("__maud_attr_value")),
+        span: Span::def_site(),
+    };
+    let value = TokenTree {
+        kind: TokenNode::Group(Delimiter::Parenthesis, value),
+        span: value_span,
+    };
+    (quote!(let Some(ref $inner_value) = $value), inner_value.into())
+}
+
+/// Returns whether the given character is whitespace in HTML. Unlike
+/// `char::is_whitespace`, this leaves out characters like U+00A0 NO-BREAK
+/// SPACE, which are displayed as text.
+fn is_whitespace(c: char) -> bool {
+    match c {
+        ' ' | '\t' | '\n' | '\r' | '\x0C' => true,
+        _ => false,
+    }
+}
+
+fn escape(s: &str, mode: Mode) -> String {
     use std::fmt::Write;
     let mut buffer = String::new();
-    Escaper::new(&mut buffer).write_str(s).unwrap();
+    match mode {
+        Mode::Html => Escaper::new(&mut buffer).write_str(s).unwrap(),
+        Mode::Xml => Escaper::xml(&mut buffer).write_str(s).unwrap(),
+    }
     buffer
 }
@@ -6,39 +6,80 @@ extern crate literalext;
 extern crate maud_htmlescape;
 extern crate proc_macro;
 
+mod namespace;
 mod parse;
 mod build;
 
-use proc_macro::{Literal, Span, Term, TokenNode, TokenStream, TokenTree};
+use proc_macro::{Diagnostic, Literal, Span, Term, TokenNode, TokenStream, TokenTree};
 use proc_macro::quote;
 
-type ParseResult<T> = Result<T, String>;
+use build::Mode;
+
+type ParseResult<T> = Result<T, Diagnostic>;
 
 #[proc_macro]
 pub fn html(input: TokenStream) -> TokenStream {
-    expand(input)
+    expand(input, Mode::Html)
 }
 
 #[proc_macro]
 pub fn html_debug(input: TokenStream) -> TokenStream {
-    let expr = expand(input);
+    let expr = expand(input, Mode::Html);
     println!("expansion:\n{}", expr);
     expr
 }
 
-fn expand(input: TokenStream) -> TokenStream {
-    let output_ident = TokenTree {
-        kind: TokenNode::Term(Term::intern("__maud_output")),
-        span: Span::def_site(),
-    };
-    // Heuristic: the size of the resulting markup tends to correlate with the
-    // code size of the template itself
-    let size_hint = input.to_string().len();
-    let size_hint = TokenNode::Literal(Literal::u64(size_hint as u64));
-    let stmts = match parse::parse(input, output_ident.clone()) {
-        Ok(stmts) => stmts,
-        Err(e) => panic!(e),
+#[proc_macro]
+pub fn html_to(input: TokenStream) -> TokenStream {
+    expand_to(input)
+}
+
+#[proc_macro]
+pub fn html_async(input: TokenStream) -> TokenStream {
+    expand_async(input)
+}
+
+#[proc_macro]
+pub fn html_fn(input: TokenStream) -> TokenStream {
+    expand_fn(input)
+}
+
+#[proc_macro]
+pub fn html_stream(input: TokenStream) -> TokenStream {
+    expand_stream(input)
+}
+
+#[proc_macro]
+pub fn xml(input: TokenStream) -> TokenStream {
+    expand(input, Mode::Xml)
+}
+
+#[proc_macro]
+pub fn html_static(input: TokenStream) -> TokenStream {
+    expand_static(input)
+}
+
+fn expand(input: TokenStream, mode: Mode) -> TokenStream {
+    let output_ident = output_ident();
+    let builder = match parse::parse(input, output_ident.clone(), mode).into_static() {
+        Ok(markup) => {
+            // With no splices or control structures, the template can be
+            // copied out in one go
+            let markup = TokenNode::Literal(Literal::string(&markup));
+            return quote!({
+                extern crate maud;
+                maud::PreEscaped(String::from($markup))
+            });
+        },
+        Err(builder) => builder,
     };
+    let size_hint = TokenNode::Literal(Literal::u64(builder.size_hint() as u64));
+    let stmts = builder.build();
+    expand_with_capacity(output_ident, size_hint, stmts)
+}
+
+#[cfg(not(feature = "learn-capacity"))]
+fn expand_with_capacity(output_ident: TokenTree, size_hint: TokenNode, stmts: TokenStream) -> TokenStream {
     quote!({
         extern crate maud;
         let mut $output_ident = String::with_capacity($size_hint as usize);
@@ -46,3 +87,127 @@ fn expand(input: TokenStream) -> TokenStream {
         maud::PreEscaped($output_ident)
     })
 }
+
+/// Like the above, but also remembers the length of the output at each call
+/// site, so that later renders can allocate enough space up front.
+#[cfg(feature = "learn-capacity")]
+fn expand_with_capacity(output_ident: TokenTree, size_hint: TokenNode, stmts: TokenStream) -> TokenStream {
+    let capacity_ident = TokenTree {
+        kind: TokenNode::Term(Term::intern("__MAUD_CAPACITY")),
+        span: Span::def_site(),
+    };
+    quote!({
+        extern crate maud;
+        static $capacity_ident: maud::macro_private::Capacity = maud::macro_private::CAPACITY_INIT;
+        let mut $output_ident = String::with_capacity($capacity_ident.get($size_hint as usize));
+        $stmts
+        $capacity_ident.update($output_ident.len());
+        maud::PreEscaped($output_ident)
+    })
+}
+
+fn expand_to(input: TokenStream) -> TokenStream {
+    let output_ident = output_ident();
+    // Split off the writer, which comes before the first top-level comma.
+    // This doesn't parse the writer as an expression, so a writer with a
+    // comma of its own, such as `foo::<A, B>()`, must be wrapped in
+    // parentheses
+    let mut input = input.into_iter();
+    let mut writer = Vec::new();
+    let mut found_comma = false;
+    while let Some(token) = input.next() {
+        match token.kind {
+            TokenNode::Op(',', _) => {
+                found_comma = true;
+                break;
+            },
+            _ => writer.push(token),
+        }
+    }
+    if !found_comma || writer.is_empty() {
+        Span::call_site()
+            .error("expected a writer, followed by a comma and the template")
+            .emit();
+        // Don't try to expand the template, since the writer is missing and
+        // that would only lead to more errors
+        return quote!({
+            let result: ::std::fmt::Result = Ok(());
+            result
+        });
+    }
+    let writer: TokenStream = writer.into_iter().collect();
+    let stmts = parse::parse(input.collect(), output_ident.clone(), Mode::Html).build();
+    quote!({
+        extern crate maud;
+        let mut $output_ident = maud::macro_private::Sink::new(&mut ($writer));
+        $stmts
+        $output_ident.finish()
+    })
+}
+
+fn expand_stream(input: TokenStream) -> TokenStream {
+    let output_ident = output_ident();
+    let stmts = parse::parse(input, output_ident.clone(), Mode::Html).build();
+    quote!({
+        extern crate maud;
+        maud::Streaming::new(move |$output_ident: &mut maud::macro_private::ChunkedSink| {
+            $stmts
+        })
+    })
+}
+
+/// Expands to an `async_block!` from `futures-await`, which the caller
+/// must have in scope, along with `await!`.
+fn expand_async(input: TokenStream) -> TokenStream {
+    let output_ident = output_ident();
+    let builder = parse::parse_async(input, output_ident.clone());
+    let size_hint = TokenNode::Literal(Literal::u64(builder.size_hint() as u64));
+    let stmts = builder.build();
+    let async_block = TokenTree {
+        kind: TokenNode::Term(Term::intern("async_block")),
+        span: Span::call_site(),
+    };
+    quote!({
+        extern crate maud;
+        $async_block! {
+            let mut $output_ident = String::with_capacity($size_hint as usize);
+            $stmts
+            Ok(maud::PreEscaped($output_ident))
+        }
+    })
+}
+
+fn expand_fn(input: TokenStream) -> TokenStream {
+    let output_ident = output_ident();
+    let stmts = parse::parse(input, output_ident.clone(), Mode::Html).build();
+    quote!({
+        extern crate maud;
+        maud::from_fn(move |$output_ident: &mut String| {
+            $stmts
+        })
+    })
+}
+
+fn expand_static(input: TokenStream) -> TokenStream {
+    let markup = match parse::parse(input, output_ident(), Mode::Html).into_static() {
+        Ok(markup) => markup,
+        Err(_) => {
+            Span::call_site()
+                .error("html_static! templates can't contain splices or control structures")
+                .emit();
+            String::new()
+        },
+    };
+    let markup = TokenNode::Literal(Literal::string(&markup));
+    quote!({
+        extern crate maud;
+        maud::PreEscaped($markup)
+    })
+}
+
+fn output_ident() -> TokenTree {
+    TokenTree {
+        kind: TokenNode::Term(Term::intern("__maud_output")),
+        span: Span::def_site(),
+    }
+}
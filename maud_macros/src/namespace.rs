@@ -0,0 +1,154 @@
+//! Knowledge of the SVG and MathML namespaces.
+//!
+//! Inside an `svg` or `math` element, names are case-sensitive, and empty
+//! elements must be self-closing. These tables let us catch misspelled names
+//! at compile time.
+
+/// The namespace of an element.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Namespace {
+    Html,
+    Svg,
+    MathMl,
+}
+
+impl Namespace {
+    /// Returns the namespace of an element with the given name, where `self`
+    /// is the namespace of its parent.
+    pub fn enter(self, name: &str) -> Namespace {
+        match (self, name) {
+            (Namespace::Html, "svg") |
+            (Namespace::MathMl, "svg") => Namespace::Svg,
+            (Namespace::Html, "math") => Namespace::MathMl,
+            _ => self,
+        }
+    }
+
+    /// Returns the namespace of the children of an element with the given
+    /// name, where `self` is the namespace of the element itself.
+    ///
+    /// The children of the [HTML integration points] and MathML text
+    /// integration points are HTML.
+    ///
+    /// [HTML integration points]: https://html.spec.whatwg.org/multipage/parsing.html#html-integration-point
+    pub fn children(self, name: &str) -> Namespace {
+        match (self, name) {
+            (Namespace::Svg, "foreignObject") |
+            (Namespace::Svg, "desc") |
+            (Namespace::Svg, "title") |
+            (Namespace::MathMl, "annotation-xml") |
+            (Namespace::MathMl, "mi") |
+            (Namespace::MathMl, "mo") |
+            (Namespace::MathMl, "mn") |
+            (Namespace::MathMl, "ms") |
+            (Namespace::MathMl, "mtext") => Namespace::Html,
+            _ => self,
+        }
+    }
+
+    /// Returns whether this is SVG or MathML, where empty elements must be
+    /// self-closing.
+    pub fn is_foreign(self) -> bool {
+        self != Namespace::Html
+    }
+
+    /// Returns the value of the `xmlns` attribute for the root element of
+    /// this namespace.
+    pub fn xmlns(self) -> Option<&'static str> {
+        match self {
+            Namespace::Html => None,
+            Namespace::Svg => Some("http://www.w3.org/2000/svg"),
+            Namespace::MathMl => Some("http://www.w3.org/1998/Math/MathML"),
+        }
+    }
+
+    /// Checks that the given name is a known element in this namespace.
+    pub fn check_element(self, name: &str) -> Result<(), String> {
+        let (elements, namespace_name) = match self {
+            Namespace::Html => return Ok(()),
+            Namespace::Svg => (SVG_ELEMENTS, "SVG"),
+            Namespace::MathMl => (MATHML_ELEMENTS, "MathML"),
+        };
+        if name.contains(':') || elements.iter().any(|&element| element == name) {
+            return Ok(());
+        }
+        match find_ignore_case(elements, name) {
+            Some(expected) => Err(format!("unknown {} element `{}`; did you mean `{}`?",
+                                          namespace_name, name, expected)),
+            None => Err(format!("unknown {} element `{}`", namespace_name, name)),
+        }
+    }
+
+    /// Checks that the given attribute name has the right case.
+    ///
+    /// Since there are far more attributes than elements, this doesn't
+    /// reject unknown attributes; it only checks that names like `viewBox`
+    /// are capitalized correctly.
+    pub fn check_attribute(self, name: &str) -> Result<(), String> {
+        let attributes = match self {
+            Namespace::Html => return Ok(()),
+            Namespace::Svg => SVG_CAMEL_CASE_ATTRIBUTES,
+            Namespace::MathMl => MATHML_CAMEL_CASE_ATTRIBUTES,
+        };
+        match find_ignore_case(attributes, name) {
+            Some(expected) if expected != name =>
+                Err(format!("unknown attribute `{}`; did you mean `{}`?", name, expected)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn find_ignore_case(names: &[&'static str], name: &str) -> Option<&'static str> {
+    names.iter().cloned().find(|candidate| candidate.eq_ignore_ascii_case(name))
+}
+
+/// Elements in SVG 1.1 and SVG 2.
+const SVG_ELEMENTS: &[&str] = &[
+    "a", "altGlyph", "altGlyphDef", "altGlyphItem", "animate", "animateColor",
+    "animateMotion", "animateTransform", "circle", "clipPath", "cursor", "defs",
+    "desc", "discard", "ellipse", "feBlend", "feColorMatrix",
+    "feComponentTransfer", "feComposite", "feConvolveMatrix",
+    "feDiffuseLighting", "feDisplacementMap", "feDistantLight", "feDropShadow",
+    "feFlood", "feFuncA", "feFuncB", "feFuncG", "feFuncR", "feGaussianBlur",
+    "feImage", "feMerge", "feMergeNode", "feMorphology", "feOffset",
+    "fePointLight", "feSpecularLighting", "feSpotLight", "feTile",
+    "feTurbulence", "filter", "font", "font-face", "font-face-format",
+    "font-face-name", "font-face-src", "font-face-uri", "foreignObject", "g",
+    "glyph", "glyphRef", "hkern", "image", "line", "linearGradient", "marker",
+    "mask", "metadata", "missing-glyph", "mpath", "path", "pattern", "polygon",
+    "polyline", "radialGradient", "rect", "script", "set", "stop", "style",
+    "svg", "switch", "symbol", "text", "textPath", "title", "tref", "tspan",
+    "use", "view", "vkern",
+];
+
+/// Elements in MathML 3, excluding content markup.
+const MATHML_ELEMENTS: &[&str] = &[
+    "annotation", "annotation-xml", "maction", "maligngroup", "malignmark",
+    "math", "menclose", "merror", "mfenced", "mfrac", "mglyph", "mi",
+    "mlabeledtr", "mlongdiv", "mmultiscripts", "mn", "mo", "mover", "mpadded",
+    "mphantom", "mprescripts", "mroot", "mrow", "ms", "mscarries", "mscarry",
+    "msgroup", "msline", "mspace", "msqrt", "msrow", "mstack", "mstyle", "msub",
+    "msubsup", "msup", "mtable", "mtd", "mtext", "mtr", "munder", "munderover",
+    "none", "semantics",
+];
+
+/// SVG attributes with upper case letters in their names.
+const SVG_CAMEL_CASE_ATTRIBUTES: &[&str] = &[
+    "attributeName", "attributeType", "baseFrequency", "baseProfile",
+    "calcMode", "clipPathUnits", "diffuseConstant", "edgeMode", "filterUnits",
+    "glyphRef", "gradientTransform", "gradientUnits", "kernelMatrix",
+    "kernelUnitLength", "keyPoints", "keySplines", "keyTimes", "lengthAdjust",
+    "limitingConeAngle", "markerHeight", "markerUnits", "markerWidth",
+    "maskContentUnits", "maskUnits", "numOctaves", "pathLength",
+    "patternContentUnits", "patternTransform", "patternUnits", "pointsAtX",
+    "pointsAtY", "pointsAtZ", "preserveAlpha", "preserveAspectRatio",
+    "primitiveUnits", "refX", "refY", "repeatCount", "repeatDur",
+    "requiredExtensions", "requiredFeatures", "specularConstant",
+    "specularExponent", "spreadMethod", "startOffset", "stdDeviation",
+    "stitchTiles", "surfaceScale", "systemLanguage", "tableValues", "targetX",
+    "targetY", "textLength", "viewBox", "viewTarget", "xChannelSelector",
+    "yChannelSelector", "zoomAndPan",
+];
+
+/// MathML attributes with upper case letters in their names.
+const MATHML_CAMEL_CASE_ATTRIBUTES: &[&str] = &["definitionURL"];
@@ -1,5 +1,6 @@
 use proc_macro::{
     Delimiter,
+    Diagnostic,
     Literal,
     Spacing,
     Span,
@@ -12,22 +13,55 @@ use std::iter;
 use std::mem;
 
 use literalext::LiteralExt;
+use maud_htmlescape::{is_valid_attribute_name, is_valid_element_name};
 
-use super::build::Builder;
+use super::build::{self, Builder, Context, Mode};
+use super::namespace::Namespace;
 use super::ParseResult;
 
-pub fn parse(input: TokenStream, output_ident: TokenTree) -> ParseResult<TokenStream> {
-    let mut parser = Parser::new(input, output_ident);
+/// Elements whose contents are whitespace-sensitive, and so are never
+/// collapsed.
+const PRESERVE_WHITESPACE: &[&str] = &["pre", "textarea", "script", "style"];
+
+/// Parses the body of an `html!` invocation.
+///
+/// Syntax errors are reported as they are found. In that case the returned
+/// code is a best-effort expansion of the parts that did parse, so that errors
+/// in splices can be reported in the same build.
+pub fn parse(input: TokenStream, output_ident: TokenTree, mode: Mode) -> Builder {
+    let mut parser = Parser::new(input, output_ident, mode);
+    let mut builder = parser.builder();
+    parser.markups(&mut builder);
+    builder
+}
+
+/// Like `parse`, but for the body of an `html_async!` invocation, which may
+/// contain `@for await` loops.
+pub fn parse_async(input: TokenStream, output_ident: TokenTree) -> Builder {
+    let mut parser = Parser::new(input, output_ident, Mode::Html);
+    parser.is_async = true;
     let mut builder = parser.builder();
-    parser.markups(&mut builder)?;
-    Ok(builder.build())
+    parser.markups(&mut builder);
+    builder
 }
 
 #[derive(Clone)]
 struct Parser {
     output_ident: TokenTree,
+    mode: Mode,
+    /// The namespace of the enclosing element.
+    namespace: Namespace,
     /// Indicates whether we're inside an attribute node.
     in_attr: bool,
+    /// Indicates whether whitespace in string literals should be collapsed.
+    collapse: bool,
+    /// How splices should be escaped.
+    context: Context,
+    /// Indicates whether we're in an `html_async!` invocation.
+    is_async: bool,
+    /// The span of the enclosing group, used for reporting an unexpected end
+    /// of input.
+    span: Span,
     input: TokenTreeIter,
 }
 
@@ -40,24 +74,36 @@ impl Iterator for Parser {
 }
 
 impl Parser {
-    fn new(input: TokenStream, output_ident: TokenTree) -> Parser {
+    fn new(input: TokenStream, output_ident: TokenTree, mode: Mode) -> Parser {
         Parser {
             output_ident,
+            mode,
+            namespace: Namespace::Html,
             in_attr: false,
+            collapse: false,
+            context: Context::Text,
+            is_async: false,
+            span: Span::call_site(),
             input: input.into_iter(),
         }
     }
 
-    fn with_input(&self, input: TokenStream) -> Parser {
+    fn with_input(&self, input: TokenStream, span: Span) -> Parser {
         Parser {
             output_ident: self.output_ident.clone(),
+            mode: self.mode,
+            namespace: self.namespace,
             in_attr: self.in_attr,
+            collapse: self.collapse,
+            context: self.context,
+            is_async: self.is_async,
+            span,
             input: input.into_iter(),
         }
     }
 
     fn builder(&self) -> Builder {
-        Builder::new(self.output_ident.clone())
+        Builder::new(self.output_ident.clone(), self.mode)
     }
 
     /// Returns the next token in the stream without consuming it.
@@ -87,17 +133,50 @@ impl Parser {
         *self = attempt;
     }
 
-    /// Returns an `Err` with the given message.
-    fn error<T, E: Into<String>>(&self, message: E) -> ParseResult<T> {
-        Err(message.into())
+    /// Returns an `Err` with the given message, pointing at the given span.
+    fn error<T, E: Into<String>>(&self, span: Span, message: E) -> ParseResult<T> {
+        Err(span.error(message))
+    }
+
+    /// Returns an `Err` for an unexpected end of input, pointing at the
+    /// enclosing group.
+    fn eof_error<T, E: Into<String>>(&self, message: E) -> ParseResult<T> {
+        self.error(self.span, message)
+    }
+
+    /// Returns the span of the next token, or of the enclosing group if there
+    /// are no tokens left.
+    fn peek_span(&mut self) -> Span {
+        self.peek().map_or(self.span, |token| token.span)
+    }
+
+    /// Reports the given error, then skips ahead to the next `;` or braced
+    /// block so that parsing can carry on from there.
+    fn recover(&mut self, error: Diagnostic) {
+        error.emit();
+        loop {
+            match self.next() {
+                None |
+                Some(TokenTree { kind: TokenNode::Op(';', _), .. }) |
+                Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, _), .. }) => break,
+                Some(_) => {},
+            }
+        }
     }
 
     /// Parses and renders multiple blocks of markup.
-    fn markups(&mut self, builder: &mut Builder) -> ParseResult<()> {
+    ///
+    /// Errors are reported and recovered from, rather than returned.
+    fn markups(&mut self, builder: &mut Builder) {
         loop {
             match self.peek2() {
-                None => return Ok(()),
+                None => return,
                 Some((TokenTree { kind: TokenNode::Op(';', _), .. }, _)) => self.advance(),
+                Some((TokenTree { kind: TokenNode::Op('~', _), .. }, _)) => {
+                    // Trim the whitespace on either side
+                    self.advance();
+                    builder.trim();
+                },
                 Some((
                     TokenTree { kind: TokenNode::Op('@', _), .. },
                     Some(TokenTree { kind: TokenNode::Term(term), span }),
@@ -106,18 +185,22 @@ impl Parser {
                     // new block to avoid scoping issues
                     let keyword = TokenTree { kind: TokenNode::Term(term), span };
                     self.advance2();
-                    builder.push({
-                        let mut builder = self.builder();
-                        builder.push(keyword);
-                        self.let_expr(&mut builder)?;
-                        self.markups(&mut builder)?;
-                        TokenTree {
-                            kind: TokenNode::Group(Delimiter::Brace, builder.build()),
-                            span,
-                        }
-                    });
+                    let mut let_builder = self.builder();
+                    let_builder.push(keyword);
+                    match self.let_expr(&mut let_builder) {
+                        Ok(()) => {
+                            self.markups(&mut let_builder);
+                            builder.push(TokenTree {
+                                kind: TokenNode::Group(Delimiter::Brace, let_builder.build()),
+                                span,
+                            });
+                        },
+                        Err(e) => self.recover(e),
+                    }
+                },
+                _ => if let Err(e) = self.markup(builder) {
+                    self.recover(e);
                 },
-                _ => self.markup(builder)?,
             }
         }
     }
@@ -126,68 +209,155 @@ impl Parser {
     fn markup(&mut self, builder: &mut Builder) -> ParseResult<()> {
         let token = match self.peek() {
             Some(token) => token,
-            None => return self.error("unexpected end of input"),
+            None => return self.eof_error("unexpected end of input"),
         };
         match token {
             // Literal
-            TokenTree { kind: TokenNode::Literal(lit), .. } => {
+            TokenTree { kind: TokenNode::Literal(lit), span } => {
                 self.advance();
-                self.literal(lit, builder)?;
+                self.literal(lit, span, builder)?;
             },
             // Special form
-            TokenTree { kind: TokenNode::Op('@', _), .. } => {
+            TokenTree { kind: TokenNode::Op('@', _), span: at_span } => {
                 self.advance();
                 match self.next() {
+                    Some(TokenTree { kind: TokenNode::Term(term), span }) if term.as_str() == "tag" => {
+                        self.tag_expr(span, builder)?;
+                    },
+                    Some(TokenTree { kind: TokenNode::Term(term), span }) if term.as_str() == "collapse" => {
+                        self.collapse_block(span, builder)?;
+                    },
+                    Some(TokenTree { kind: TokenNode::Term(term), span }) if term.as_str().starts_with('\'') => {
+                        let label = TokenTree { kind: TokenNode::Term(term), span };
+                        let mut expr_builder = self.builder();
+                        self.labeled_loop(label, &mut expr_builder)?;
+                        builder.push(expr_builder.build());
+                    },
                     Some(TokenTree { kind: TokenNode::Term(term), span }) => {
                         let keyword = TokenTree { kind: TokenNode::Term(term), span };
-                        builder.push(keyword);
+                        // Render into a separate builder, so that an error
+                        // halfway through doesn't leave a partial expression
+                        // in the output
+                        let mut expr_builder = self.builder();
                         match term.as_str() {
-                            "if" => self.if_expr(builder)?,
-                            "while" => self.while_expr(builder)?,
-                            "for" => self.for_expr(builder)?,
-                            "match" => self.match_expr(builder)?,
-                            "let" => return self.error(format!("@let only works inside a block")),
-                            other => return self.error(format!("unknown keyword `@{}`", other)),
+                            "if" => self.if_expr(keyword, &mut expr_builder)?,
+                            "while" => self.while_expr(vec![keyword], &mut expr_builder)?,
+                            "for" => self.for_expr(vec![keyword], &mut expr_builder)?,
+                            "match" => self.match_expr(keyword, &mut expr_builder)?,
+                            "break" | "continue" => self.break_expr(keyword, &mut expr_builder)?,
+                            "flush" => self.flush_expr(span, &mut expr_builder)?,
+                            "let" => return self.error(span, "@let only works inside a block"),
+                            other => return self.error(span, format!("unknown keyword `@{}`", other)),
                         }
+                        builder.push(expr_builder.build());
                     },
-                    _ => return self.error("expected keyword after `@`"),
+                    Some(token) => return self.error(token.span, "expected keyword after `@`"),
+                    None => return self.error(at_span, "expected keyword after `@`"),
                 }
             }
             // Element
-            TokenTree { kind: TokenNode::Term(_), .. } => {
+            TokenTree { kind: TokenNode::Term(_), span } => {
+                if self.in_attr {
+                    return self.error(span, "unexpected element, you silly bumpkin");
+                }
                 let name = self.namespaced_name()?;
+                if let Err(message) = self.namespace.enter(&name).check_element(&name) {
+                    return self.error(span, message);
+                }
                 self.element(&name, builder)?;
             },
             // Splice
             TokenTree { kind: TokenNode::Group(Delimiter::Parenthesis, expr), .. } => {
                 self.advance();
-                builder.splice(expr);
+                builder.splice_in(expr, self.context);
             }
             // Block
-            TokenTree { kind: TokenNode::Group(Delimiter::Brace, block), .. } => {
+            TokenTree { kind: TokenNode::Group(Delimiter::Brace, block), span } => {
                 self.advance();
-                self.with_input(block).markups(builder)?;
+                self.with_input(block, span).markups(builder);
             },
             // ???
-            _ => return self.error("invalid syntax"),
+            TokenTree { span, .. } => return self.error(span, "invalid syntax"),
         }
         Ok(())
     }
 
     /// Parses and renders a literal string.
-    fn literal(&mut self, lit: Literal, builder: &mut Builder) -> ParseResult<()> {
+    fn literal(&mut self, lit: Literal, span: Span, builder: &mut Builder) -> ParseResult<()> {
         if let Some(s) = lit.parse_string() {
-            builder.string(&s);
+            if self.collapse {
+                builder.string_collapsed(&s);
+            } else {
+                builder.string(&s);
+            }
             Ok(())
         } else {
-            self.error("expected string")
+            self.error(span, "expected string")
+        }
+    }
+
+    /// Parses and renders a `@collapse` block, where whitespace in string
+    /// literals is collapsed.
+    ///
+    /// The leading `@collapse` should already be consumed.
+    fn collapse_block(&mut self, keyword_span: Span, builder: &mut Builder) -> ParseResult<()> {
+        match self.next() {
+            Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, body), span }) => {
+                let mut parser = self.with_input(body, span);
+                parser.collapse = true;
+                parser.markups(builder);
+                Ok(())
+            },
+            Some(token) => self.error(token.span, "expected body for @collapse"),
+            None => self.error(keyword_span, "expected body for @collapse"),
         }
     }
 
     /// Parses and renders an `@if` expression.
     ///
     /// The leading `@if` should already be consumed.
-    fn if_expr(&mut self, builder: &mut Builder) -> ParseResult<()> {
+    fn if_expr(&mut self, keyword: TokenTree, builder: &mut Builder) -> ParseResult<()> {
+        builder.push(keyword);
+        self.cond_and_body("@if", builder)?;
+        self.else_if_expr(builder)
+    }
+
+    /// Parses and renders the condition and body of an `@if` or `@while`.
+    ///
+    /// Usually the first braced group is the body. But with `if let` and
+    /// `while let`, any braces before the `=` are part of the pattern, as in
+    /// `@if let Point { x, y } = point { ... }`.
+    fn cond_and_body(&mut self, keyword_name: &str, builder: &mut Builder) -> ParseResult<()> {
+        if let Some(TokenTree { kind: TokenNode::Term(term), .. }) = self.peek() {
+            if term.as_str() == "let" {
+                let mut after_joint = false;
+                loop {
+                    let token = match self.next() {
+                        Some(token) => token,
+                        None => return self.eof_error(format!("unexpected end of {} expression", keyword_name)),
+                    };
+                    // Look for an `=` that isn't part of `==`, `=>`, `<=`,
+                    // `..=` and so on
+                    let is_eq = !after_joint && match token.kind {
+                        TokenNode::Op('=', Spacing::Alone) => true,
+                        TokenNode::Op('=', Spacing::Joint) => match self.peek() {
+                            Some(TokenTree { kind: TokenNode::Op('=', _), .. }) |
+                            Some(TokenTree { kind: TokenNode::Op('>', _), .. }) => false,
+                            _ => true,
+                        },
+                        _ => false,
+                    };
+                    after_joint = match token.kind {
+                        TokenNode::Op(_, Spacing::Joint) => true,
+                        _ => false,
+                    };
+                    builder.push(token);
+                    if is_eq {
+                        break;
+                    }
+                }
+            }
+        }
         loop {
             match self.next() {
                 Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, block), span }) => {
@@ -196,10 +366,10 @@ impl Parser {
                     break;
                 },
                 Some(token) => builder.push(token),
-                None => return self.error("unexpected end of @if expression"),
+                None => return self.eof_error(format!("unexpected end of {} expression", keyword_name)),
             }
         }
-        self.else_if_expr(builder)
+        Ok(())
     }
 
     /// Parses and renders an optional `@else if` or `@else`.
@@ -221,17 +391,16 @@ impl Parser {
                     if if_keyword.as_str() == "if" => {
                         self.advance();
                         let if_keyword = TokenTree { kind: TokenNode::Term(if_keyword), span };
-                        builder.push(if_keyword);
-                        self.if_expr(builder)?;
+                        self.if_expr(if_keyword, builder)?;
                     },
                     // Just an `@else`
-                    _ => {
-                        if let Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, block), span }) = self.next() {
+                    _ => match self.next() {
+                        Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, block), span }) => {
                             let block = self.block(block, span)?;
                             builder.push(block);
-                        } else {
-                            return self.error("expected body for @else");
-                        }
+                        },
+                        Some(token) => return self.error(token.span, "expected body for @else"),
+                        None => return self.error(span, "expected body for @else"),
                     },
                 }
                 self.else_if_expr(builder)
@@ -243,58 +412,155 @@ impl Parser {
 
     /// Parses and renders an `@while` expression.
     ///
-    /// The leading `@while` should already be consumed.
-    fn while_expr(&mut self, builder: &mut Builder) -> ParseResult<()> {
-        loop {
-            match self.next() {
-                Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, block), span }) => {
-                    let block = self.block(block, span)?;
-                    builder.push(block);
-                    break;
-                },
-                Some(token) => builder.push(token),
-                None => return self.error("unexpected end of @while expression"),
-            }
-        }
-        Ok(())
+    /// The leading `@while` should already be consumed, and passed in as
+    /// `head` along with its label, if any.
+    fn while_expr(&mut self, head: Vec<TokenTree>, builder: &mut Builder) -> ParseResult<()> {
+        builder.push(head.into_iter().collect::<TokenStream>());
+        self.cond_and_body("@while", builder)
     }
 
-    /// Parses and renders a `@for` expression.
+    /// Parses and renders a `@for` expression, along with an optional
+    /// `@empty` block.
     ///
-    /// The leading `@for` should already be consumed.
-    fn for_expr(&mut self, builder: &mut Builder) -> ParseResult<()> {
+    /// The leading `@for` should already be consumed, and passed in as `head`
+    /// along with its label, if any.
+    fn for_expr(&mut self, mut head: Vec<TokenTree>, builder: &mut Builder) -> ParseResult<()> {
+        // `@for await` loops over a `Stream` instead
+        let is_await = match self.peek() {
+            Some(TokenTree { kind: TokenNode::Term(term), span }) if term.as_str() == "await" => {
+                if !self.is_async {
+                    return self.error(span, "@for await only works in html_async!");
+                }
+                self.advance();
+                true
+            },
+            _ => false,
+        };
+        let prefix_len = head.len();
         loop {
             match self.next() {
                 Some(TokenTree { kind: TokenNode::Term(in_keyword), span }) if in_keyword.as_str() == "in" => {
-                    builder.push(TokenTree { kind: TokenNode::Term(in_keyword), span });
+                    head.push(TokenTree { kind: TokenNode::Term(in_keyword), span });
                     break;
                 },
-                Some(token) => builder.push(token),
-                None => return self.error("unexpected end of @for expression"),
+                Some(token) => head.push(token),
+                None => return self.eof_error("unexpected end of @for expression"),
             }
         }
-        loop {
+        let mut iter = Vec::new();
+        let (body, body_size_hint) = loop {
             match self.next() {
                 Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, block), span }) => {
-                    let block = self.block(block, span)?;
-                    builder.push(block);
-                    break;
+                    break self.block_sized(block, span)?;
                 },
-                Some(token) => builder.push(token),
-                None => return self.error("unexpected end of @for expression"),
+                Some(token) => iter.push(token),
+                None => return self.eof_error("unexpected end of @for expression"),
             }
+        };
+        if is_await {
+            // Split the head into the label (if any) and the pattern,
+            // dropping the `in` and `for` keywords
+            head.pop();
+            let pat = head.split_off(prefix_len).into_iter().collect();
+            head.pop();
+            builder.emit_for_await(head.into_iter().collect(), pat, iter.into_iter().collect(), body);
+            return Ok(());
+        }
+        // Reserve space for the whole loop up front, where possible
+        head.extend(builder.reserve_for(iter.into_iter().collect(), body_size_hint));
+        let head: TokenStream = head.into_iter().collect();
+        match self.peek2() {
+            Some((
+                TokenTree { kind: TokenNode::Op('@', _), .. },
+                Some(TokenTree { kind: TokenNode::Term(empty_keyword), span }),
+            )) if empty_keyword.as_str() == "empty" => {
+                self.advance2();
+                let empty = match self.next() {
+                    Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, block), span }) => {
+                        self.block(block, span)?
+                    },
+                    Some(token) => return self.error(token.span, "expected body for @empty"),
+                    None => return self.error(span, "expected body for @empty"),
+                };
+                builder.emit_for_empty(head, body, empty);
+            },
+            _ => {
+                builder.push(head);
+                builder.push(body);
+            },
+        }
+        Ok(())
+    }
+
+    /// Parses and renders a labeled `@for` or `@while` loop, as in
+    /// `@'outer: for x in xs { ... }`.
+    ///
+    /// The leading `@` and label should already be consumed.
+    fn labeled_loop(&mut self, label: TokenTree, builder: &mut Builder) -> ParseResult<()> {
+        let colon = match self.next() {
+            Some(colon @ TokenTree { kind: TokenNode::Op(':', _), .. }) => colon,
+            Some(token) => return self.error(token.span, "expected `:` after loop label"),
+            None => return self.error(label.span, "expected `:` after loop label"),
+        };
+        match self.next() {
+            Some(TokenTree { kind: TokenNode::Term(term), span }) if term.as_str() == "for" => {
+                let keyword = TokenTree { kind: TokenNode::Term(term), span };
+                self.for_expr(vec![label, colon, keyword], builder)
+            },
+            Some(TokenTree { kind: TokenNode::Term(term), span }) if term.as_str() == "while" => {
+                let keyword = TokenTree { kind: TokenNode::Term(term), span };
+                self.while_expr(vec![label, colon, keyword], builder)
+            },
+            Some(token) => self.error(token.span, "expected `for` or `while` after loop label"),
+            None => self.error(colon.span, "expected `for` or `while` after loop label"),
+        }
+    }
+
+    /// Parses and renders a `@break` or `@continue` expression, with an
+    /// optional label.
+    ///
+    /// The leading `@break` or `@continue` should already be consumed.
+    fn break_expr(&mut self, keyword: TokenTree, builder: &mut Builder) -> ParseResult<()> {
+        let span = keyword.span;
+        builder.push(keyword);
+        match self.peek() {
+            Some(TokenTree { kind: TokenNode::Term(label), span })
+            if label.as_str().starts_with('\'') => {
+                self.advance();
+                builder.push(TokenTree { kind: TokenNode::Term(label), span });
+            },
+            _ => {},
+        }
+        if let Some(TokenTree { kind: TokenNode::Op(';', _), .. }) = self.peek() {
+            self.advance();
         }
+        builder.push(TokenTree { kind: TokenNode::Op(';', Spacing::Alone), span });
+        Ok(())
+    }
+
+    /// Parses and renders a `@flush` marker.
+    ///
+    /// The leading `@flush` should already be consumed.
+    fn flush_expr(&mut self, keyword_span: Span, builder: &mut Builder) -> ParseResult<()> {
+        if self.in_attr {
+            return self.error(keyword_span, "@flush can't be used inside an attribute");
+        }
+        if let Some(TokenTree { kind: TokenNode::Op(';', _), .. }) = self.peek() {
+            self.advance();
+        }
+        builder.flush_point();
         Ok(())
     }
 
     /// Parses and renders a `@match` expression.
     ///
     /// The leading `@match` should already be consumed.
-    fn match_expr(&mut self, builder: &mut Builder) -> ParseResult<()> {
+    fn match_expr(&mut self, keyword: TokenTree, builder: &mut Builder) -> ParseResult<()> {
+        builder.push(keyword);
         loop {
             match self.next() {
                 Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, body), span }) => {
-                    let body = self.with_input(body).match_arms()?;
+                    let body = self.with_input(body, span).match_arms()?;
                     builder.push(TokenTree {
                         kind: TokenNode::Group(Delimiter::Brace, body),
                         span,
@@ -302,7 +568,7 @@ impl Parser {
                     break;
                 },
                 Some(token) => builder.push(token),
-                None => return self.error("unexpected end of @match expression"),
+                None => return self.eof_error("unexpected end of @match expression"),
             }
         }
         Ok(())
@@ -337,7 +603,7 @@ impl Parser {
                     if pat.is_empty() {
                         return Ok(None);
                     } else {
-                        return self.error("unexpected end of @match pattern");
+                        return self.eof_error("unexpected end of @match pattern");
                     },
             }
         }
@@ -364,12 +630,12 @@ impl Parser {
                             }
                             body.push(token);
                         },
-                        None => return self.error("unexpected end of @match arm"),
+                        None => return self.eof_error("unexpected end of @match arm"),
                     }
                 }
                 self.block(body.into_iter().collect(), span)?
             },
-            None => return self.error("unexpected end of @match arm"),
+            None => return self.eof_error("unexpected end of @match arm"),
         };
         Ok(Some(pat.into_iter().chain(iter::once(body)).collect()))
     }
@@ -385,7 +651,7 @@ impl Parser {
                     break;
                 },
                 Some(token) => builder.push(token),
-                None => return self.error("unexpected end of @let expression"),
+                None => return self.eof_error("unexpected end of @let expression"),
             }
         }
         loop {
@@ -395,7 +661,7 @@ impl Parser {
                     break;
                 },
                 Some(token) => builder.push(token),
-                None => return self.error("unexpected end of @let expression"),
+                None => return self.eof_error("unexpected end of @let expression"),
             }
         }
         Ok(())
@@ -405,19 +671,56 @@ impl Parser {
     ///
     /// The element name should already be consumed.
     fn element(&mut self, name: &str, builder: &mut Builder) -> ParseResult<()> {
-        if self.in_attr {
-            return self.error("unexpected element, you silly bumpkin");
-        }
+        // Parse the element under its own namespace, which may differ from
+        // that of its parent
+        let parent = self.namespace;
+        let collapse = self.collapse;
+        let context = self.context;
+        self.namespace = parent.enter(name);
+        let result = self.element_in_namespace(name, parent, builder);
+        self.namespace = parent;
+        self.collapse = collapse;
+        self.context = context;
+        result
+    }
+
+    fn element_in_namespace(
+        &mut self,
+        name: &str,
+        parent: Namespace,
+        builder: &mut Builder,
+    ) -> ParseResult<()> {
         builder.element_open_start(name);
-        self.attrs(builder)?;
-        builder.element_open_end();
+        let attr_names = self.attrs(builder)?;
+        if self.namespace != parent && !attr_names.iter().any(|attr_name| attr_name == "xmlns") {
+            // The root of an SVG or MathML fragment
+            if let Some(xmlns) = self.namespace.xmlns() {
+                builder.attribute_start("xmlns");
+                builder.string(xmlns);
+                builder.attribute_end();
+            }
+        }
+        let is_foreign = self.namespace.is_foreign();
         match self.peek() {
             Some(TokenTree { kind: TokenNode::Op(';', _), .. }) |
             Some(TokenTree { kind: TokenNode::Op('/', _), .. }) => {
                 // Void element
                 self.advance();
+                builder.element_open_end_void(is_foreign);
+            },
+            Some(TokenTree { kind: TokenNode::Group(Delimiter::Brace, ref body), .. })
+            if is_foreign && body.is_empty() => {
+                // Empty SVG or MathML element
+                self.advance();
+                builder.element_open_end_void(true);
             },
             _ => {
+                builder.element_open_end();
+                self.namespace = self.namespace.children(name);
+                if PRESERVE_WHITESPACE.iter().any(|&element| element == name) {
+                    self.collapse = false;
+                }
+                self.context = Context::element(name, self.mode);
                 self.markup(builder)?;
                 builder.element_close(name);
             },
@@ -425,31 +728,122 @@ impl Parser {
         Ok(())
     }
 
+    /// Parses and renders a `@tag` expression, which is an element with a name
+    /// computed at runtime, or written as a string literal.
+    ///
+    /// The leading `@tag` should already be consumed.
+    fn tag_expr(&mut self, keyword_span: Span, builder: &mut Builder) -> ParseResult<()> {
+        if self.in_attr {
+            return self.error(keyword_span, "unexpected element, you silly bumpkin");
+        }
+        // A string literal names the element at compile time, as in
+        // `@tag "my-el-2" { ... }`
+        if let Some(TokenTree { kind: TokenNode::Literal(lit), span }) = self.peek() {
+            if let Some(name) = lit.parse_string() {
+                self.advance();
+                if !is_valid_element_name(&name) {
+                    return self.error(span, format!("invalid element name `{}`", name));
+                }
+                if let Err(message) = self.namespace.enter(&name).check_element(&name) {
+                    return self.error(span, message);
+                }
+                return self.element(&name, builder);
+            }
+        }
+        let (name, name_span) = match self.next() {
+            Some(TokenTree { kind: TokenNode::Group(Delimiter::Parenthesis, name), span }) => (name, span),
+            Some(token) => return self.error(token.span, "expected string literal or parenthesized element name after @tag"),
+            None => return self.error(keyword_span, "expected string literal or parenthesized element name after @tag"),
+        };
+        let mut element_builder = self.builder();
+        element_builder.element_open_start_spliced();
+        self.attrs(&mut element_builder)?;
+        match self.peek() {
+            Some(TokenTree { kind: TokenNode::Op(';', _), .. }) |
+            Some(TokenTree { kind: TokenNode::Op('/', _), .. }) => {
+                // Void element
+                self.advance();
+                element_builder.element_open_end_void(self.namespace.is_foreign());
+            },
+            _ => {
+                element_builder.element_open_end();
+                self.markup(&mut element_builder)?;
+                element_builder.element_close_spliced();
+            },
+        }
+        builder.emit_tag(name, name_span, element_builder.build());
+        Ok(())
+    }
+
     /// Parses and renders the attributes of an element.
-    fn attrs(&mut self, builder: &mut Builder) -> ParseResult<()> {
-        let mut classes_static = Vec::new();
+    ///
+    /// Returns the names of the attributes, not including spreads.
+    fn attrs(&mut self, builder: &mut Builder) -> ParseResult<Vec<String>> {
+        let mut attr_names = Vec::new();
+        let mut classes_always = Vec::new();
         let mut classes_toggled = Vec::new();
-        let mut ids = Vec::new();
+        let mut id = None;
+        let mut has_id = false;
+        let mut has_spread = false;
         loop {
+            let span = self.peek_span();
             let mut attempt = self.clone();
-            let maybe_name = attempt.namespaced_name();
+            let maybe_name = attempt.attr_name();
             let token_after = attempt.next();
             match (maybe_name, token_after) {
+                // Invalid attribute name
+                (Ok(ref name), Some(TokenTree { kind: TokenNode::Op('=', _), .. })) |
+                (Ok(ref name), Some(TokenTree { kind: TokenNode::Op('?', _), .. }))
+                if !is_valid_attribute_name(name) => {
+                    return self.error(span, format!("invalid attribute name `{}`", name));
+                },
+                // Class attribute, to be merged with the class shorthands
+                (Ok(ref name), Some(TokenTree { kind: TokenNode::Op('=', _), .. }))
+                if name == "class" => {
+                    self.commit(attempt);
+                    attr_names.push(name.clone());
+                    if let Some((value, value_span)) = self.attr_toggler() {
+                        // Add the class only if the value is `Some`
+                        let (cond, inner_value) = build::if_let_some(value, value_span);
+                        classes_toggled.push((cond, value_span, ClassOrId::Splice(inner_value)));
+                    } else {
+                        classes_always.push(self.class_or_id_value("class")?);
+                    }
+                },
+                // ID attribute, to be checked against the ID shorthand
+                (Ok(ref name), Some(TokenTree { kind: TokenNode::Op('=', _), .. }))
+                if name == "id" => {
+                    self.commit(attempt);
+                    attr_names.push(name.clone());
+                    if has_id {
+                        return self.error(span, "an element can only have one id");
+                    }
+                    has_id = true;
+                    if let Some((value, value_span)) = self.attr_toggler() {
+                        // Emit the attribute only if the value is `Some`
+                        builder.attribute_optional("id", value, value_span);
+                    } else {
+                        id = Some(self.class_or_id_value("id")?);
+                    }
+                },
                 // Non-empty attribute
                 (Ok(name), Some(TokenTree { kind: TokenNode::Op('=', _), .. })) => {
                     self.commit(attempt);
-                    builder.attribute_start(&name);
-                    {
-                        // Parse a value under an attribute context
-                        let in_attr = mem::replace(&mut self.in_attr, true);
-                        self.markup(builder)?;
-                        self.in_attr = in_attr;
+                    self.check_attr_name(&name, span)?;
+                    if let Some((value, value_span)) = self.attr_toggler() {
+                        // Emit the attribute only if the value is `Some`
+                        builder.attribute_optional(&name, value, value_span);
+                    } else {
+                        builder.attribute_start(&name);
+                        self.attr_value(&name, builder)?;
+                        builder.attribute_end();
                     }
-                    builder.attribute_end();
+                    attr_names.push(name);
                 },
                 // Empty attribute
                 (Ok(name), Some(TokenTree { kind: TokenNode::Op('?', _), .. })) => {
                     self.commit(attempt);
+                    self.check_attr_name(&name, span)?;
                     if let Some((cond, cond_span)) = self.attr_toggler() {
                         // Toggle the attribute based on a boolean expression
                         let body = {
@@ -462,55 +856,170 @@ impl Parser {
                         // Write the attribute unconditionally
                         builder.attribute_empty(&name);
                     }
+                    attr_names.push(name);
+                },
+                // Attribute spread
+                (Err(_), Some(TokenTree { kind: TokenNode::Op('.', Spacing::Joint), .. })) => {
+                    match (attempt.next(), attempt.next()) {
+                        (
+                            Some(TokenTree { kind: TokenNode::Op('.', _), .. }),
+                            Some(TokenTree { kind: TokenNode::Group(Delimiter::Parenthesis, expr), .. }),
+                        ) => {
+                            self.commit(attempt);
+                            if !has_spread {
+                                builder.spread_start();
+                                has_spread = true;
+                            }
+                            builder.attribute_spread(expr);
+                        },
+                        _ => return self.error(span, "expected attribute spread `..(expr)`"),
+                    }
                 },
                 // Class shorthand
                 (Err(_), Some(TokenTree { kind: TokenNode::Op('.', _), .. })) => {
                     self.commit(attempt);
-                    let class_name = self.name()?;
+                    let class_name = self.shorthand()?;
                     if let Some((cond, cond_span)) = self.attr_toggler() {
                         // Toggle the class based on a boolean expression
                         classes_toggled.push((cond, cond_span, class_name));
                     } else {
                         // Emit the class unconditionally
-                        classes_static.push(class_name);
+                        classes_always.push(class_name);
                     }
                 },
                 // ID shorthand
                 (Err(_), Some(TokenTree { kind: TokenNode::Op('#', _), .. })) => {
                     self.commit(attempt);
-                    ids.push(self.name()?);
+                    if has_id {
+                        return self.error(span, "an element can only have one id");
+                    }
+                    has_id = true;
+                    id = Some(self.shorthand()?);
                 },
                 // If it's not a valid attribute, backtrack and bail out
                 _ => break,
             }
         }
-        if !classes_static.is_empty() || !classes_toggled.is_empty() {
+        let has_classes_always = !classes_always.is_empty();
+        if has_classes_always {
             builder.attribute_start("class");
-            builder.string(&classes_static.join(" "));
-            for (i, (cond, cond_span, mut class_name)) in classes_toggled.into_iter().enumerate() {
-                // If a class comes first in the list, then it shouldn't be
-                // prefixed by a space
-                if i > 0 || !classes_static.is_empty() {
-                    class_name = format!(" {}", class_name);
+            for (i, class_name) in classes_always.into_iter().enumerate() {
+                if i > 0 {
+                    builder.string(" ");
                 }
+                class_name.render(builder);
+            }
+        }
+        if !classes_toggled.is_empty() || has_spread {
+            // Which of these come first is only known at runtime, and if
+            // they're all toggled off then the attribute is left out
+            builder.class_list_start(has_classes_always);
+            for (cond, cond_span, class_name) in classes_toggled {
                 let body = {
                     let mut builder = self.builder();
-                    builder.string(&class_name);
+                    builder.class_separator();
+                    class_name.render(&mut builder);
                     builder.build()
                 };
                 builder.emit_if(cond, cond_span, body);
             }
+            if has_spread {
+                builder.spread_class();
+            }
+            if !has_classes_always {
+                builder.class_list_end();
+            }
+        }
+        if has_classes_always {
             builder.attribute_end();
         }
-        if !ids.is_empty() {
+        if let Some(id) = id {
             builder.attribute_start("id");
-            builder.string(&ids.join(" "));
+            id.render(builder);
             builder.attribute_end();
+        } else if has_spread && !has_id {
+            // The ID in the template takes precedence over the spreads
+            builder.spread_id();
         }
+        Ok(attr_names)
+    }
+
+    /// Checks an attribute name against the namespace of the element.
+    fn check_attr_name(&self, name: &str, span: Span) -> ParseResult<()> {
+        match self.namespace.check_attribute(name) {
+            Ok(()) => Ok(()),
+            Err(message) => self.error(span, message),
+        }
+    }
+
+    /// Parses and renders the value of an attribute.
+    fn attr_value(&mut self, name: &str, builder: &mut Builder) -> ParseResult<()> {
+        let attr_context = Context::attribute(name, self.mode);
+        let is_splice = match self.peek() {
+            Some(TokenTree { kind: TokenNode::Group(Delimiter::Parenthesis, _), .. }) => true,
+            _ => false,
+        };
+        if attr_context == Context::Url && !is_splice {
+            return self.url_attr_value(builder);
+        }
+        // Parse the value under an attribute context
+        let in_attr = mem::replace(&mut self.in_attr, true);
+        let context = mem::replace(&mut self.context, attr_context);
+        let result = self.markup(builder);
+        self.in_attr = in_attr;
+        self.context = context;
+        result
+    }
+
+    /// Parses and renders the value of a URL attribute which is made up of
+    /// more than a single splice, as in `href={ "/ponies/" (name) }`.
+    ///
+    /// Since the scheme of the URL may come from any of its parts, the value
+    /// is rendered to a separate buffer, where it's checked as a whole.
+    fn url_attr_value(&mut self, builder: &mut Builder) -> ParseResult<()> {
+        let url_ident = build::url_ident();
+        let output_ident = mem::replace(&mut self.output_ident, url_ident);
+        let in_attr = mem::replace(&mut self.in_attr, true);
+        let context = mem::replace(&mut self.context, Context::Text);
+        let mut url_builder = self.builder();
+        let result = self.markup(&mut url_builder);
+        self.output_ident = output_ident;
+        self.in_attr = in_attr;
+        self.context = context;
+        result?;
+        builder.url_value(url_builder);
         Ok(())
     }
 
-    /// Parses the `[cond]` syntax after an empty attribute or class shorthand.
+    /// Parses the name in a class or ID shorthand, which is either an
+    /// identifier or a parenthesized expression.
+    fn shorthand(&mut self) -> ParseResult<ClassOrId> {
+        match self.peek() {
+            Some(TokenTree { kind: TokenNode::Group(Delimiter::Parenthesis, expr), .. }) => {
+                self.advance();
+                Ok(ClassOrId::Splice(expr))
+            },
+            _ => self.name().map(ClassOrId::Static),
+        }
+    }
+
+    /// Parses the value of an explicit `class` or `id` attribute.
+    fn class_or_id_value(&mut self, name: &str) -> ParseResult<ClassOrId> {
+        if let Some(TokenTree { kind: TokenNode::Literal(lit), .. }) = self.peek() {
+            // Keep string literals as they are, so that they can be merged
+            // with the other static text in the template
+            if let Some(s) = lit.parse_string() {
+                self.advance();
+                return Ok(ClassOrId::Static(s));
+            }
+        }
+        let mut builder = self.builder();
+        self.attr_value(name, &mut builder)?;
+        Ok(ClassOrId::Markup(builder.build()))
+    }
+
+    /// Parses the `[cond]` syntax after an empty attribute or class shorthand,
+    /// or the `[value]` syntax of an optional attribute.
     fn attr_toggler(&mut self) -> Option<(TokenStream, Span)> {
         if let Some(TokenTree {
             kind: TokenNode::Group(Delimiter::Bracket, cond),
@@ -529,7 +1038,8 @@ impl Parser {
             self.advance();
             String::from(term.as_str())
         } else {
-            return self.error("expected identifier");
+            let span = self.peek_span();
+            return self.error(span, "expected identifier");
         };
         let mut expect_ident = false;
         loop {
@@ -544,6 +1054,13 @@ impl Parser {
                     s.push_str(term.as_str());
                     false
                 },
+                // Allow numbers after a hyphen, as in `data-2col`
+                Some(TokenTree { kind: TokenNode::Literal(ref lit), .. })
+                if expect_ident && lit.to_string().chars().all(char::is_alphanumeric) => {
+                    self.advance();
+                    s.push_str(&lit.to_string());
+                    false
+                },
                 _ => break,
             };
         }
@@ -562,14 +1079,54 @@ impl Parser {
         Ok(s)
     }
 
+    /// Parses an attribute name, which may be written as a string literal
+    /// if it's not a valid identifier.
+    fn attr_name(&mut self) -> ParseResult<String> {
+        if let Some(TokenTree { kind: TokenNode::Literal(lit), .. }) = self.peek() {
+            if let Some(name) = lit.parse_string() {
+                self.advance();
+                return Ok(name);
+            }
+        }
+        self.namespaced_name()
+    }
+
     /// Parses the given token stream as a Maud expression, returning a block of
     /// Rust code.
     fn block(&mut self, body: TokenStream, span: Span) -> ParseResult<TokenTree> {
+        self.block_sized(body, span).map(|(block, _)| block)
+    }
+
+    /// Like `block`, but also returns an estimate of the size of its output.
+    fn block_sized(&mut self, body: TokenStream, span: Span) -> ParseResult<(TokenTree, usize)> {
         let mut builder = self.builder();
-        self.with_input(body).markups(&mut builder)?;
-        Ok(TokenTree {
+        self.with_input(body, span).markups(&mut builder);
+        let size_hint = builder.size_hint();
+        Ok((TokenTree {
             kind: TokenNode::Group(Delimiter::Brace, builder.build()),
             span,
-        })
+        }, size_hint))
+    }
+}
+
+/// A class name or ID, taken from either a shorthand or an explicit
+/// attribute.
+enum ClassOrId {
+    /// A name written out in the template, as in `.foo` or `class="foo"`.
+    Static(String),
+    /// An expression to be rendered at runtime, as in `.(foo)`.
+    Splice(TokenStream),
+    /// Statements which render an arbitrary attribute value, as in
+    /// `class=@if foo { "bar" }`.
+    Markup(TokenStream),
+}
+
+impl ClassOrId {
+    fn render(self, builder: &mut Builder) {
+        match self {
+            ClassOrId::Static(name) => builder.string(&name),
+            ClassOrId::Splice(expr) => builder.splice(expr),
+            ClassOrId::Markup(stmts) => builder.push(stmts),
+        }
     }
 }